@@ -0,0 +1,174 @@
+use crate::matches::Matches;
+use star::Score;
+use std::{collections::HashMap, mem::size_of, sync::Arc};
+
+struct Entry<'a> {
+    scores: Arc<Matches<'a>>,
+    size: usize,
+    last_used: u64,
+}
+
+// caches the scores for each query, evicting the least recently used queries once the memory used
+// by the cached scores would exceed `capacity` bytes.
+pub struct ScoreCache<'a> {
+    entries: HashMap<String, Entry<'a>>,
+    capacity: usize,
+    size: usize,
+    clock: u64,
+}
+
+fn entry_size(query: &str, scores: &Matches) -> usize {
+    size_of::<Entry>() + query.len() + scores.len() * size_of::<Score>()
+}
+
+impl<'a> ScoreCache<'a> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            capacity,
+            size: 0,
+            clock: 0,
+        }
+    }
+
+    pub fn contains_key(&self, query: &str) -> bool {
+        self.entries.contains_key(query)
+    }
+
+    pub fn get(&mut self, query: &str) -> Option<Arc<Matches<'a>>> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(query)?;
+        entry.last_used = self.clock;
+        Some(Arc::clone(&entry.scores))
+    }
+
+    // returns the cached scores with the fewest matches among the queries accepted by `usable`
+    pub fn smallest(&mut self, usable: impl Fn(&str) -> bool) -> Option<Arc<Matches<'a>>> {
+        let query = self
+            .entries
+            .iter()
+            .filter(|(query, _)| usable(query))
+            .min_by_key(|(_, entry)| entry.scores.len())
+            .map(|(query, _)| query.clone())?;
+        self.get(&query)
+    }
+
+    pub fn insert(&mut self, query: String, scores: Arc<Matches<'a>>) {
+        let size = entry_size(&query, &scores);
+        if let Some(old) = self.entries.remove(&query) {
+            self.size -= old.size;
+        }
+        if size > self.capacity {
+            return;
+        }
+        while self.size + size > self.capacity {
+            self.evict();
+        }
+        self.clock += 1;
+        self.size += size;
+        self.entries.insert(
+            query,
+            Entry {
+                scores,
+                size,
+                last_used: self.clock,
+            },
+        );
+    }
+
+    fn evict(&mut self) {
+        let oldest = self
+            .entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(query, _)| query.clone());
+        if let Some(entry) = oldest.and_then(|query| self.entries.remove(&query)) {
+            self.size -= entry.size;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use star::{Line, Matcher, Selecta};
+
+    fn scores<'a>(lines: &'a [Line], query: &[char]) -> Arc<Matches<'a>> {
+        Arc::new(
+            lines
+                .iter()
+                .filter_map(|l| Selecta::default().score(l, query))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_insert_and_get() {
+        let lines = [
+            Line::new("foo".to_string(), 0),
+            Line::new("bar".to_string(), 1),
+            Line::new("baz".to_string(), 2),
+        ];
+        let mut cache = ScoreCache::new(usize::MAX);
+        assert_eq!(cache.get("b"), None);
+        let b = scores(&lines, &['b']);
+        cache.insert("b".to_string(), Arc::clone(&b));
+        assert!(cache.contains_key("b"));
+        assert_eq!(cache.get("b"), Some(b));
+        assert_eq!(cache.size, entry_size("b", &scores(&lines, &['b'])));
+        // replacing an entry doesn't count its size twice
+        cache.insert("b".to_string(), scores(&lines, &['b']));
+        assert_eq!(cache.size, entry_size("b", &scores(&lines, &['b'])));
+    }
+
+    #[test]
+    fn test_smallest() {
+        let lines = [
+            Line::new("foo".to_string(), 0),
+            Line::new("bar".to_string(), 1),
+            Line::new("baz".to_string(), 2),
+        ];
+        let mut cache = ScoreCache::new(usize::MAX);
+        assert_eq!(cache.smallest(|_| true), None);
+        cache.insert("".to_string(), scores(&lines, &[]));
+        cache.insert("b".to_string(), scores(&lines, &['b']));
+        cache.insert("z".to_string(), scores(&lines, &['z']));
+        assert_eq!(cache.smallest(|_| true), Some(scores(&lines, &['z'])));
+        assert_eq!(cache.smallest(|q| q != "z"), Some(scores(&lines, &['b'])));
+        assert_eq!(cache.smallest(|q| q.is_empty()), Some(scores(&lines, &[])));
+        assert_eq!(cache.smallest(|_| false), None);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let lines = [
+            Line::new("foo".to_string(), 0),
+            Line::new("bar".to_string(), 1),
+            Line::new("baz".to_string(), 2),
+        ];
+        let size = entry_size("b", &scores(&lines, &['b']));
+        let mut cache = ScoreCache::new(size * 2);
+        cache.insert("a".to_string(), scores(&lines, &['a']));
+        cache.insert("b".to_string(), scores(&lines, &['b']));
+        // using "a" makes "b" the least recently used
+        cache.get("a");
+        cache.insert("z".to_string(), scores(&lines, &['z']));
+        assert!(cache.contains_key("a"));
+        assert!(!cache.contains_key("b"));
+        assert!(cache.contains_key("z"));
+        assert!(cache.size <= size * 2);
+    }
+
+    #[test]
+    fn test_too_large_for_capacity() {
+        let lines = [
+            Line::new("foo".to_string(), 0),
+            Line::new("bar".to_string(), 1),
+            Line::new("baz".to_string(), 2),
+        ];
+        let mut cache = ScoreCache::new(1);
+        cache.insert("b".to_string(), scores(&lines, &['b']));
+        assert!(!cache.contains_key("b"));
+        assert_eq!(cache.size, 0);
+    }
+}
@@ -1,32 +1,153 @@
+use crate::theme;
 use clap::ArgMatches;
-use std::io::{Error, ErrorKind, Result};
+use std::{
+    collections::HashMap,
+    env,
+    io::{Error, ErrorKind, Result},
+};
 use termion::{color, style};
 
+// the --color-* options without their prefix, which is also how themes name the colors they set
+pub const COLOR_KEYS: [&str; 12] = [
+    "normal-fg",
+    "normal-bg",
+    "matched-fg",
+    "matched-bg",
+    "selected-fg",
+    "selected-bg",
+    "matched-selected-fg",
+    "matched-selected-bg",
+    "tag-fg",
+    "tag-bg",
+    "header-fg",
+    "header-bg",
+];
+
 pub struct Colors {
     pub normal: String,
     pub selected: String,
     pub matched: String,
     pub matched_selected: String,
     pub tag: String,
+    pub header: String,
 }
 
-fn default_fg() -> Colors {
-    Colors {
-        normal: format!("{}", color::Fg(color::Reset)),
-        selected: format!("{}{}", color::Fg(color::Reset), style::Invert),
-        matched: format!("{}", color::Fg(color::Red)),
-        matched_selected: format!("{}", color::Fg(color::Red)),
-        tag: format!("{}", color::Fg(color::LightBlue)),
+// how many colors the terminal can show
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Depth {
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl Depth {
+    // terminals that can show any RGB color say so in $COLORTERM. others are assumed to manage 256
+    // colors if $TERM says so (e.g. xterm-256color), and only 16 otherwise
+    fn detect(colorterm: Option<&str>, term: Option<&str>) -> Self {
+        match colorterm {
+            Some("truecolor") | Some("24bit") => Depth::TrueColor,
+            _ if term.is_some_and(|t| t.contains("256")) => Depth::Ansi256,
+            _ => Depth::Ansi16,
+        }
     }
 }
 
-fn default_bg() -> Colors {
-    Colors {
-        normal: format!("{}", color::Bg(color::Reset)),
-        selected: String::new(),
-        matched: String::new(),
-        matched_selected: String::new(),
-        tag: String::new(),
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Color {
+    Reset,
+    Ansi(u8),
+    Rgb(u8, u8, u8),
+}
+
+#[derive(Clone, Copy)]
+enum Layer {
+    Fg,
+    Bg,
+}
+
+// xterm's default values for the 16 basic colors, for finding the closest one to an RGB color.
+// terminals are free to change them, but they rarely stray far from their names
+const ANSI_16: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+// the levels of each component in the 6x6x6 color cube that makes up colors 16-231
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).unsigned_abs().pow(2);
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+// the RGB value of one of the 256 colors
+fn ansi_rgb(n: u8) -> (u8, u8, u8) {
+    match n {
+        0..=15 => ANSI_16[usize::from(n)],
+        16..=231 => {
+            let i = usize::from(n - 16);
+            (
+                CUBE_LEVELS[i / 36],
+                CUBE_LEVELS[i / 6 % 6],
+                CUBE_LEVELS[i % 6],
+            )
+        }
+        _ => {
+            let level = 8 + 10 * (n - 232);
+            (level, level, level)
+        }
+    }
+}
+
+// the closest of colors 16-255, which (unlike the first 16) look the same in every terminal
+fn nearest_256(rgb: (u8, u8, u8)) -> u8 {
+    (16..=255)
+        .min_by_key(|n| distance(rgb, ansi_rgb(*n)))
+        .unwrap()
+}
+
+fn nearest_16(rgb: (u8, u8, u8)) -> u8 {
+    (0..16).min_by_key(|n| distance(rgb, ansi_rgb(*n))).unwrap()
+}
+
+// returns the escape sequence that sets the color, downgraded to one the terminal can show
+fn escape(color: Color, layer: Layer, depth: Depth) -> String {
+    let color = match (color, depth) {
+        (Color::Rgb(r, g, b), Depth::Ansi256) => Color::Ansi(nearest_256((r, g, b))),
+        (Color::Rgb(r, g, b), Depth::Ansi16) => Color::Ansi(nearest_16((r, g, b))),
+        (Color::Ansi(n), Depth::Ansi16) if n >= 16 => Color::Ansi(nearest_16(ansi_rgb(n))),
+        (c, _) => c,
+    };
+    match (color, layer) {
+        (Color::Reset, Layer::Fg) => format!("{}", color::Fg(color::Reset)),
+        (Color::Reset, Layer::Bg) => format!("{}", color::Bg(color::Reset)),
+        // the 16 colors have their own codes, which 16 color terminals may be limited to
+        (Color::Ansi(n), _) if depth == Depth::Ansi16 => {
+            let base = match layer {
+                Layer::Fg => 30,
+                Layer::Bg => 40,
+            };
+            let code = if n < 8 { base + n } else { base + 60 + n - 8 };
+            format!("\x1b[{}m", code)
+        }
+        (Color::Ansi(n), Layer::Fg) => format!("{}", color::Fg(color::AnsiValue(n))),
+        (Color::Ansi(n), Layer::Bg) => format!("{}", color::Bg(color::AnsiValue(n))),
+        (Color::Rgb(r, g, b), Layer::Fg) => format!("{}", color::Fg(color::Rgb(r, g, b))),
+        (Color::Rgb(r, g, b), Layer::Bg) => format!("{}", color::Bg(color::Rgb(r, g, b))),
     }
 }
 
@@ -53,62 +174,164 @@ fn parse_rgb_color(color: &str) -> Result<color::Rgb> {
     Ok(color::Rgb(red, green, blue))
 }
 
-macro_rules! parse_color {
-    ($color:ident, $attr:tt) => {
-        if $color.starts_with('#') {
-            Ok(format!("{}", color::$attr(parse_rgb_color($color)?)))
-        } else if let Ok(n) = $color.parse::<u8>() {
-            Ok(format!("{}", color::$attr(color::AnsiValue(n))))
+fn parse_color(spec: &str) -> Result<Color> {
+    if spec.starts_with('#') {
+        let color::Rgb(r, g, b) = parse_rgb_color(spec)?;
+        return Ok(Color::Rgb(r, g, b));
+    }
+    if let Ok(n) = spec.parse::<u8>() {
+        return Ok(Color::Ansi(n));
+    }
+    let n = match spec.to_lowercase().as_str() {
+        "default" | "reset" => return Ok(Color::Reset),
+        "black" => 0,
+        "red" => 1,
+        "green" => 2,
+        "yellow" => 3,
+        "blue" => 4,
+        "magenta" => 5,
+        "cyan" => 6,
+        "white" => 7,
+        "lightblack" => 8,
+        "lightred" => 9,
+        "lightgreen" => 10,
+        "lightyellow" => 11,
+        "lightblue" => 12,
+        "lightmagenta" => 13,
+        "lightcyan" => 14,
+        "lightwhite" => 15,
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid color specification: \"{}\"", spec),
+            ));
+        }
+    };
+    Ok(Color::Ansi(n))
+}
+
+fn parse_attribute(spec: &str) -> Option<String> {
+    Some(match spec.to_lowercase().as_str() {
+        "bold" => format!("{}", style::Bold),
+        "dim" => format!("{}", style::Faint),
+        "italic" => format!("{}", style::Italic),
+        "underline" => format!("{}", style::Underline),
+        "reverse" => format!("{}", style::Invert),
+        _ => return None,
+    })
+}
+
+// a style is a color, any number of attributes or both, separated by colons (e.g.
+// "red:bold:underline" or "underline")
+fn parse_style(spec: &str, layer: Layer, depth: Depth) -> Result<String> {
+    let mut color = None;
+    let mut attributes = String::new();
+    for part in spec.split(':') {
+        if let Some(attribute) = parse_attribute(part) {
+            attributes.push_str(&attribute);
+        } else if color.is_none() {
+            color = Some(parse_color(part)?);
+        } else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("more than one color in style specification: \"{}\"", spec),
+            ));
+        }
+    }
+    let color = color.map_or_else(String::new, |c| escape(c, layer, depth));
+    Ok(color + &attributes)
+}
+
+fn default_fg(depth: Depth, no_color: bool) -> Colors {
+    let fg = |c| {
+        if no_color {
+            String::new()
         } else {
-            Ok(match $color.to_lowercase().as_str() {
-                "black" => format!("{}", color::$attr(color::Black)),
-                "blue" => format!("{}", color::$attr(color::Blue)),
-                "cyan" => format!("{}", color::$attr(color::Cyan)),
-                "green" => format!("{}", color::$attr(color::Green)),
-                "lightblack" => format!("{}", color::$attr(color::LightBlack)),
-                "lightblue" => format!("{}", color::$attr(color::LightBlue)),
-                "lightcyan" => format!("{}", color::$attr(color::LightCyan)),
-                "lightgreen" => format!("{}", color::$attr(color::LightGreen)),
-                "lightmagenta" => format!("{}", color::$attr(color::LightMagenta)),
-                "lightred" => format!("{}", color::$attr(color::LightRed)),
-                "lightwhite" => format!("{}", color::$attr(color::LightWhite)),
-                "lightyellow" => format!("{}", color::$attr(color::LightYellow)),
-                "magenta" => format!("{}", color::$attr(color::Magenta)),
-                "red" => format!("{}", color::$attr(color::Red)),
-                "white" => format!("{}", color::$attr(color::White)),
-                "yellow" => format!("{}", color::$attr(color::Yellow)),
-                _ => {
-                    return Err(Error::new(
-                        ErrorKind::InvalidInput,
-                        format!("invalid color specification: \"{}\"", $color),
-                    ));
-                }
-            })
-        } as Result<String>
+            escape(c, Layer::Fg, depth)
+        }
+    };
+    // without colors, matches are underlined instead
+    let matched = if no_color {
+        format!("{}", style::Underline)
+    } else {
+        fg(Color::Ansi(1))
     };
+    Colors {
+        normal: fg(Color::Reset),
+        selected: format!("{}{}", fg(Color::Reset), style::Invert),
+        matched: matched.clone(),
+        matched_selected: matched,
+        tag: fg(Color::Ansi(12)),
+        header: fg(Color::Ansi(6)),
+    }
+}
+
+fn default_bg(depth: Depth, no_color: bool) -> Colors {
+    Colors {
+        normal: if no_color {
+            String::new()
+        } else {
+            escape(Color::Reset, Layer::Bg, depth)
+        },
+        selected: String::new(),
+        matched: String::new(),
+        matched_selected: String::new(),
+        tag: String::new(),
+        header: String::new(),
+    }
 }
 
 pub fn get_colors(matches: &ArgMatches) -> Result<(Colors, Colors)> {
-    let mut fg = default_fg();
-    let mut bg = default_bg();
-    if let Some(c) = matches.value_of("color-normal-fg") {
-        fg.normal = parse_color!(c, Fg)?;
+    let depth = Depth::detect(
+        env::var("COLORTERM").ok().as_deref(),
+        env::var("TERM").ok().as_deref(),
+    );
+    // see https://no-color.org. a theme or color given on the command line still applies
+    let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let mut specs = match matches.value_of("theme") {
+        Some(name) => theme::find(name)?,
+        None => HashMap::new(),
+    };
+    for key in COLOR_KEYS {
+        if let Some(spec) = matches.value_of(format!("color-{}", key)) {
+            specs.insert(key.to_string(), spec.to_string());
+        }
+    }
+    build_colors(&specs, depth, no_color)
+}
+
+// builds the colors from the defaults and `specs`, keyed like COLOR_KEYS
+pub fn build_colors(
+    specs: &HashMap<String, String>,
+    depth: Depth,
+    no_color: bool,
+) -> Result<(Colors, Colors)> {
+    let mut fg = default_fg(depth, no_color);
+    let mut bg = default_bg(depth, no_color);
+    let parse = |key: &str, layer| -> Result<Option<String>> {
+        match specs.get(key) {
+            Some(spec) => parse_style(spec, layer, depth).map(Some),
+            None => Ok(None),
+        }
+    };
+    if let Some(c) = parse("normal-fg", Layer::Fg)? {
+        fg.normal = c;
     }
-    if let Some(c) = matches.value_of("color-matched-fg") {
-        fg.matched = parse_color!(c, Fg)?;
+    if let Some(c) = parse("matched-fg", Layer::Fg)? {
+        fg.matched = c;
     }
-    if let Some(c) = matches.value_of("color-selected-fg") {
-        fg.selected = parse_color!(c, Fg)?;
+    if let Some(c) = parse("selected-fg", Layer::Fg)? {
+        fg.selected = c;
     }
-    if let Some(c) = matches.value_of("color-normal-bg") {
-        bg.normal = parse_color!(c, Bg)?;
+    if let Some(c) = parse("normal-bg", Layer::Bg)? {
+        bg.normal = c;
     }
-    if let Some(c) = matches.value_of("color-matched-bg") {
-        bg.matched = parse_color!(c, Bg)?;
+    if let Some(c) = parse("matched-bg", Layer::Bg)? {
+        bg.matched = c;
     }
-    if let Some(c) = matches.value_of("color-selected-bg") {
-        bg.selected = parse_color!(c, Bg)?;
-        if matches.value_of("color-selected-fg").is_none() {
+    if let Some(c) = parse("selected-bg", Layer::Bg)? {
+        bg.selected = c;
+        if !specs.contains_key("selected-fg") {
             // if we set a background color on the selected line but don't set a foreground color,
             // then we need to clear the default foreground style::Invert. by setting it to the
             // same style as the normal fg, we ensure highlighting works correctly on selected
@@ -117,22 +340,26 @@ pub fn get_colors(matches: &ArgMatches) -> Result<(Colors, Colors)> {
             fg.selected = fg.normal.clone();
         }
     }
-    if let Some(c) = matches.value_of("color-matched-selected-fg") {
-        fg.matched_selected = parse_color!(c, Fg)?;
-    } else {
-        fg.matched_selected = fg.matched.clone();
+    match parse("matched-selected-fg", Layer::Fg)? {
+        Some(c) => fg.matched_selected = c,
+        None => fg.matched_selected = fg.matched.clone(),
     }
-    if let Some(c) = matches.value_of("color-matched-selected-bg") {
-        bg.matched_selected = parse_color!(c, Bg)?;
-    } else {
-        bg.matched_selected = bg.selected.clone();
+    match parse("matched-selected-bg", Layer::Bg)? {
+        Some(c) => bg.matched_selected = c,
+        None => bg.matched_selected = bg.selected.clone(),
     }
-    if let Some(c) = matches.value_of("color-tag-fg") {
-        fg.tag = parse_color!(c, Fg)?;
+    if let Some(c) = parse("tag-fg", Layer::Fg)? {
+        fg.tag = c;
+    }
+    if let Some(c) = parse("tag-bg", Layer::Bg)? {
+        bg.tag = c;
+    }
+    if let Some(c) = parse("header-fg", Layer::Fg)? {
+        fg.header = c;
+    }
+    if let Some(c) = parse("header-bg", Layer::Bg)? {
+        bg.header = c;
     }
-    if let Some(c) = matches.value_of("color-tag-bg") {
-        bg.tag = parse_color!(c, Bg)?;
-    };
     Ok((fg, bg))
 }
 
@@ -156,20 +383,96 @@ mod tests {
         assert!(parse_rgb_color("#z7z7z7").is_err());
     }
 
-    fn test_parse_color_macro_helper(color: &str) -> Result<String> {
-        parse_color!(color, Fg)
+    fn fg(spec: &str, depth: Depth) -> Result<String> {
+        parse_style(spec, Layer::Fg, depth)
     }
 
     #[test]
-    fn test_parse_color_macro() {
+    fn test_parse_color() {
         assert_eq!(
             format!("{}", color::Fg(color::Red)),
-            test_parse_color_macro_helper("red").unwrap()
+            fg("red", Depth::Ansi256).unwrap()
         );
         assert_eq!(
             format!("{}", color::Fg(color::LightBlack)),
-            test_parse_color_macro_helper("LIGHTBLACK").unwrap()
+            fg("LIGHTBLACK", Depth::Ansi256).unwrap()
+        );
+        assert!(fg("awoijf", Depth::Ansi256).is_err());
+    }
+
+    #[test]
+    fn test_detect_depth() {
+        assert_eq!(
+            Depth::detect(Some("truecolor"), Some("xterm")),
+            Depth::TrueColor
+        );
+        assert_eq!(Depth::detect(Some("24bit"), None), Depth::TrueColor);
+        assert_eq!(Depth::detect(None, Some("xterm-256color")), Depth::Ansi256);
+        assert_eq!(
+            Depth::detect(Some(""), Some("screen-256color")),
+            Depth::Ansi256
+        );
+        assert_eq!(Depth::detect(None, Some("xterm")), Depth::Ansi16);
+        assert_eq!(Depth::detect(None, None), Depth::Ansi16);
+    }
+
+    #[test]
+    fn test_downgrade() {
+        assert_eq!(
+            fg("#ff8700", Depth::TrueColor).unwrap(),
+            "\x1b[38;2;255;135;0m"
+        );
+        // exact matches in the color cube and the grays
+        assert_eq!(fg("#ff8700", Depth::Ansi256).unwrap(), "\x1b[38;5;208m");
+        assert_eq!(fg("#808080", Depth::Ansi256).unwrap(), "\x1b[38;5;244m");
+        assert_eq!(fg("#010203", Depth::Ansi256).unwrap(), "\x1b[38;5;16m");
+        assert_eq!(fg("#ee0000", Depth::Ansi16).unwrap(), "\x1b[91m");
+        assert_eq!(fg("#002b36", Depth::Ansi16).unwrap(), "\x1b[30m");
+        assert_eq!(fg("208", Depth::Ansi16).unwrap(), "\x1b[33m");
+        assert_eq!(fg("blue", Depth::Ansi16).unwrap(), "\x1b[34m");
+        assert_eq!(
+            escape(Color::Ansi(14), Layer::Bg, Depth::Ansi16),
+            "\x1b[106m"
+        );
+        assert_eq!(fg("default", Depth::Ansi16).unwrap(), "\x1b[39m");
+    }
+
+    #[test]
+    fn test_build_colors() {
+        let specs: HashMap<String, String> = [("selected-bg", "#3a3a3a"), ("matched-fg", "green")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let (fg, bg) = build_colors(&specs, Depth::Ansi256, false).unwrap();
+        assert_eq!(bg.selected, "\x1b[48;5;237m");
+        // the default inverse video gives way to the background color
+        assert_eq!(fg.selected, fg.normal);
+        assert_eq!(fg.matched_selected, "\x1b[38;5;2m");
+        assert_eq!(bg.matched_selected, bg.selected);
+        let (fg, bg) = build_colors(&HashMap::new(), Depth::Ansi256, true).unwrap();
+        assert_eq!(fg.matched, format!("{}", style::Underline));
+        assert_eq!(fg.selected, format!("{}", style::Invert));
+        assert_eq!(bg.normal, "");
+        let (fg, _) = build_colors(&specs, Depth::Ansi256, true).unwrap();
+        assert_eq!(fg.matched, "\x1b[38;5;2m");
+    }
+
+    #[test]
+    fn test_parse_style() {
+        assert_eq!(
+            fg("red:bold:underline", Depth::Ansi256).unwrap(),
+            "\x1b[38;5;1m\x1b[1m\x1b[4m"
+        );
+        assert_eq!(
+            fg("Italic:#ff8700", Depth::Ansi256).unwrap(),
+            "\x1b[38;5;208m\x1b[3m"
+        );
+        assert_eq!(fg("dim", Depth::Ansi256).unwrap(), "\x1b[2m");
+        assert_eq!(
+            parse_style("underline:lightred", Layer::Bg, Depth::Ansi16).unwrap(),
+            "\x1b[101m\x1b[4m"
         );
-        assert!(test_parse_color_macro_helper("awoijf").is_err());
+        assert!(fg("red:blue", Depth::Ansi256).is_err());
+        assert!(fg("red:blink", Depth::Ansi256).is_err());
     }
 }
@@ -1,23 +1,42 @@
-use libc::{c_ushort, ioctl, TIOCGWINSZ};
+use crate::screen::Screen;
+use libc::{c_ushort, ioctl, poll, pollfd, POLLIN, TIOCGWINSZ};
 use std::{
+    cell::RefCell,
+    cmp::min,
     fs::File,
-    io::{self, Write},
+    io::{self, Read, Write},
     mem,
     os::unix::io::{AsRawFd, RawFd},
+    sync::atomic::{AtomicBool, Ordering},
 };
 use termion::{self, cursor};
 use termios::{cfmakeraw, tcsetattr, Termios, ECHO, ICANON, TCSANOW};
 
+const POLL_TIMEOUT_MS: i32 = 100;
+// how long to wait for the terminal to say where the cursor is
+const CURSOR_TIMEOUT_MS: i32 = 500;
+// report button presses and the scroll wheel, with coordinates in the sgr encoding
+const ENABLE_MOUSE: &str = "\x1b[?1000h\x1b[?1006h";
+const DISABLE_MOUSE: &str = "\x1b[?1006l\x1b[?1000l";
+
 #[derive(Debug)]
 pub struct Console {
     pub width: u16,
     pub height: u16,
     original_state: Termios,
     pub tty: File,
+    screen: RefCell<Screen>,
+    mouse: bool,
+    // the terminal row (counting from 1) the ui started on, if mouse reporting is enabled and the
+    // terminal said where that was
+    origin: Option<u16>,
+    // input read while waiting for the terminal to say where the cursor was, which is read again
+    // before anything else
+    typed: RefCell<Vec<u8>>,
 }
 
 impl Console {
-    pub fn new() -> io::Result<Self> {
+    pub fn new(mouse: bool) -> io::Result<Self> {
         let tty = termion::get_tty()?;
         let (width, height) = terminal_size(tty.as_raw_fd())?;
         let mut termios = Termios::from_fd(tty.as_raw_fd())?;
@@ -27,27 +46,154 @@ impl Console {
         termios.c_lflag &= !(ECHO | ICANON);
         tcsetattr(tty.as_raw_fd(), TCSANOW, &termios)?;
 
-        Ok(Self {
+        let mut console = Self {
             width,
             height,
             original_state,
             tty,
-        })
+            screen: RefCell::new(Screen::new()),
+            mouse,
+            origin: None,
+            typed: RefCell::new(vec![]),
+        };
+        // mouse reporting is only enabled once there's a console to disable it again when dropped,
+        // however this goes
+        if mouse {
+            write!(&console.tty, "{}", ENABLE_MOUSE)?;
+            let (origin, typed) = cursor_row(&console.tty);
+            console.origin = origin;
+            console.typed = RefCell::new(typed);
+        }
+        Ok(console)
     }
 
-    pub fn write(&self, buf: &str) {
-        let mut tty = termion::get_tty().unwrap();
+    // returns which row of the frame is on terminal row `y` (counting from 1), if any. the ui
+    // starts where the cursor was, unless drawing it made the terminal scroll.
+    pub fn frame_row(&self, y: u16) -> Option<usize> {
+        let origin = self.origin?;
+        let most_rows = self.screen.borrow().most_rows() as u16;
+        let top = min(origin, (self.height + 1).saturating_sub(most_rows));
+        y.checked_sub(top).map(usize::from)
+    }
+
+    // draws a frame, leaving the cursor on `cursor_row`
+    pub fn draw(&self, frame: Vec<String>, cursor_row: usize) {
+        let output = self.screen.borrow_mut().diff(frame, cursor_row);
+        self.write(&output);
+    }
+
+    pub fn clear(&self) {
+        let output = self.screen.borrow_mut().clear();
+        self.write(&output);
+    }
+
+    pub fn input<'a>(&'a self, done: &'a AtomicBool) -> Input<'a> {
+        Input {
+            tty: &self.tty,
+            done,
+            typed: self.typed.take(),
+        }
+    }
+
+    fn write(&self, buf: &str) {
+        let mut tty = &self.tty;
         write!(tty, "{}{}{}", cursor::Hide, buf, cursor::Show).unwrap();
         tty.flush().unwrap();
     }
+
+    fn write_raw(&self, buf: &str) {
+        let mut tty = &self.tty;
+        let _ = write!(tty, "{}", buf).and_then(|_| tty.flush());
+    }
 }
 
 impl Drop for Console {
     fn drop(&mut self) {
+        if self.mouse {
+            self.write_raw(DISABLE_MOUSE);
+        }
         tcsetattr(self.tty.as_raw_fd(), TCSANOW, &self.original_state).unwrap();
     }
 }
 
+// reads from the tty, but wakes up periodically to check whether the event loop has finished so
+// that a thread blocked on input can be joined. once `done` is set, reads report end of file.
+pub struct Input<'a> {
+    tty: &'a File,
+    done: &'a AtomicBool,
+    // read before the tty
+    typed: Vec<u8>,
+}
+
+impl Read for Input<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.typed.is_empty() {
+            let n = min(buf.len(), self.typed.len());
+            buf[..n].copy_from_slice(&self.typed[..n]);
+            self.typed.drain(..n);
+            return Ok(n);
+        }
+        let mut fds = pollfd {
+            fd: self.tty.as_raw_fd(),
+            events: POLLIN,
+            revents: 0,
+        };
+        while !self.done.load(Ordering::SeqCst) {
+            match unsafe { poll(&mut fds, 1, POLL_TIMEOUT_MS) } {
+                -1 => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+                0 => {}
+                _ => return self.tty.read(buf),
+            }
+        }
+        Ok(0)
+    }
+}
+
+// asks the terminal where the cursor is, returning its row (or `None` if it doesn't answer in
+// time) and anything typed before the answer
+fn cursor_row(mut tty: &File) -> (Option<u16>, Vec<u8>) {
+    let mut response = vec![];
+    if write!(tty, "\x1b[6n").and_then(|_| tty.flush()).is_err() {
+        return (None, response);
+    }
+    let mut fds = pollfd {
+        fd: tty.as_raw_fd(),
+        events: POLLIN,
+        revents: 0,
+    };
+    loop {
+        if let Some((row, start)) = parse_cursor_row(&response) {
+            response.truncate(start);
+            return (Some(row), response);
+        }
+        if unsafe { poll(&mut fds, 1, CURSOR_TIMEOUT_MS) } <= 0 {
+            return (None, response);
+        }
+        let mut byte = [0];
+        if !matches!(tty.read(&mut byte), Ok(1)) {
+            return (None, response);
+        }
+        response.push(byte[0]);
+    }
+}
+
+// parses the answer to a cursor position request, ESC [ row ; column R, at the end of what's been
+// read, returning the row and where the answer starts. anything before it was typed.
+fn parse_cursor_row(response: &[u8]) -> Option<(u16, usize)> {
+    let start = response.iter().rposition(|&b| b == 0x1b)?;
+    let answer = std::str::from_utf8(&response[start..]).ok()?;
+    let (row, _) = answer
+        .strip_prefix("\x1b[")?
+        .strip_suffix('R')?
+        .split_once(';')?;
+    Some((row.parse().ok()?, start))
+}
+
 #[repr(C)]
 struct TermSize {
     row: c_ushort,
@@ -66,3 +212,20 @@ fn terminal_size(fd: RawFd) -> io::Result<(u16, u16)> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cursor_row() {
+        assert_eq!(parse_cursor_row(b"\x1b[12;1R"), Some((12, 0)));
+        // keys typed before the answer come before it
+        assert_eq!(parse_cursor_row(b"ab\x1b[3;40R"), Some((3, 2)));
+        assert_eq!(parse_cursor_row(b"\x1b[A\x1b[3;40R"), Some((3, 3)));
+        assert_eq!(parse_cursor_row(b"\x1b[3R"), None);
+        assert_eq!(parse_cursor_row(b"R"), None);
+        // nor is an answer that's only been partly read
+        assert_eq!(parse_cursor_row(b"\x1b[3;4"), None);
+    }
+}
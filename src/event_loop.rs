@@ -1,163 +1,546 @@
 use crate::{
+    cache::ScoreCache,
     color::Colors,
     console::Console,
-    line::Line,
-    render::{Renderer, RendererConfig},
-    score::{calculate_score, Score},
+    history::History,
+    matches::Matches,
+    render::{framing_rows, Border, Counter, Renderer, RendererConfig, Spacing},
+    selection::{Selection, TaggedOrder},
+    view::View,
+    worker::{self, Job, Update},
+};
+use star::{Line, Matcher, Order, Tiebreaks};
+use std::{
+    cmp::min,
+    io,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use termion::{
+    event::{Event as TermEvent, Key, MouseButton, MouseEvent},
+    input::TermRead,
 };
-use rayon::prelude::*;
-use std::{cmp::min, collections::HashMap, io, rc::Rc};
-use termion::{event::Key, input::TermRead};
+
+// a second click on a line within this long of the first accepts it
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
 
 fn query_str(query: &[char]) -> String {
     query.iter().collect::<String>()
 }
 
-fn get_scores<'a>(
-    map: &mut HashMap<String, Rc<Vec<Score<'a>>>>,
+// returns the cached scores from which the scores for the query can be calculated. the matcher
+// decides which cached queries narrow down to the query - for subsequence matchers that's any
+// query made of a subsequence of its characters, whether it's a prefix, or the query before a
+// character was deleted from the middle. the query's own scores are preferred, then whichever
+// candidate set is smallest. `None` means nothing usable has been scored yet, so every line is a
+// candidate.
+fn find_candidates<'a>(
+    cache: &mut ScoreCache<'a>,
+    matcher: &dyn Matcher,
     query: &[char],
-) -> Rc<Vec<Score<'a>>> {
-    if let Some(scores) = &map.get(&query_str(query)) {
-        return Rc::clone(scores);
+) -> Option<Arc<Matches<'a>>> {
+    let query = query_str(query);
+    cache
+        .get(&query)
+        .or_else(|| cache.smallest(|cached| matcher.narrows(&query, cached)))
+}
+
+// returns the index of the first visible match, adjusted so that the selected match is visible
+fn scroll_offset(offset: usize, selected: usize, window: usize) -> usize {
+    if selected < offset {
+        selected
+    } else if selected >= offset + window {
+        selected + 1 - window
+    } else {
+        offset
     }
-    let mut tmp = query.to_vec();
-    loop {
-        tmp.pop();
-        if map.contains_key(&query_str(&tmp)) {
-            let mut new_scores: Vec<Score> = map
-                .get(&query_str(&tmp))
-                .unwrap()
-                .par_iter()
-                .filter_map(|s| calculate_score(s.line, query))
-                .collect();
-            new_scores.sort_unstable_by(Score::cmp);
-            let new_scores = Rc::new(new_scores);
-            map.insert(query_str(query), Rc::clone(&new_scores));
-            return new_scores;
-        }
+}
+
+// returns the header lines that fit in the `rows` rows below the search line and counter, leaving
+// room for at least one match
+fn visible_header(header: &[String], rows: usize) -> &[String] {
+    &header[..min(header.len(), rows.saturating_sub(1))]
+}
+
+enum Event<'a> {
+    Key(Key),
+    // an arrow key pressed with shift
+    Shift(Key),
+    Mouse(MouseEvent),
+    Scores(Update<'a>),
+    // reading the tty failed
+    Error(io::Error),
+}
+
+// termion doesn't recognize the sequences xterm sends for shift-arrows, so they're picked out of
+// the ones it reports as unsupported
+fn input_event<'a>(event: TermEvent) -> Option<Event<'a>> {
+    match event {
+        TermEvent::Key(key) => Some(Event::Key(key)),
+        TermEvent::Unsupported(seq) => match seq.as_slice() {
+            b"\x1b[1;2A" => Some(Event::Shift(Key::Up)),
+            b"\x1b[1;2B" => Some(Event::Shift(Key::Down)),
+            _ => None,
+        },
+        TermEvent::Mouse(mouse) => Some(Event::Mouse(mouse)),
     }
 }
 
+pub struct Config<'a> {
+    pub initial_search: &'a str,
+    pub height: usize,
+    pub colors: (Colors, Colors),
+    pub multiple: bool,
+    // the most lines that can be tagged in multiple mode
+    pub limit: Option<usize>,
+    pub tagged_order: TaggedOrder,
+    // whether enter accepts the tagged lines, like alt-enter
+    pub accept_tagged: bool,
+    // in bytes
+    pub cache_size: usize,
+    pub matcher: &'a dyn Matcher,
+    // used instead of `matcher` in regex mode
+    pub regex_matcher: &'a dyn Matcher,
+    // whether to start in regex mode
+    pub regex: bool,
+    pub tiebreaks: Tiebreaks,
+    // whether matches start out ordered by score rather than input order
+    pub sort: bool,
+    // whether to report clicks and the scroll wheel
+    pub mouse: bool,
+    // lines shown above the matches, which can't be selected
+    pub header: &'a [String],
+    pub prompt: &'a str,
+    pub pointer: &'a str,
+    pub marker: &'a str,
+    pub counter: Counter,
+    pub border: Border,
+    pub margin: Spacing,
+    pub padding: Spacing,
+}
+
+impl Config<'_> {
+    // the configured height, clamped to the console's
+    fn ui_height(&self, console: &Console) -> usize {
+        min(self.height, console.height as usize)
+    }
+
+    // the rows of a ui `height` rows tall left for the header and matches
+    fn rows(&self, height: usize) -> usize {
+        let framing = framing_rows(self.border, self.margin, self.padding);
+        height.saturating_sub(1 + self.counter.rows() + framing)
+    }
+}
+
+// accepting a query adds it to `history`, if given
 pub fn run(
     stdin_lines: Vec<Line>,
-    initial_search: &str,
-    height: usize,
-    colors: (Colors, Colors),
-    multiple: bool,
+    config: &Config,
+    history: Option<&mut History>,
 ) -> io::Result<String> {
-    let console = Console::new()?;
-    let tty = &console.tty;
-    let mut query: Vec<char> = initial_search.chars().collect();
-    let mut need_new_scores = false;
-    let mut score_map: HashMap<String, Rc<Vec<Score>>> = HashMap::new();
-    let mut tagged: Vec<usize> = vec![];
+    let console = Console::new(config.mouse)?;
+    let rows = config.rows(config.ui_height(&console));
+    let window = rows - visible_header(config.header, rows).len();
+    let (events_tx, events) = mpsc::channel();
+    let (jobs_tx, jobs) = mpsc::channel();
+    let generation = AtomicUsize::new(0);
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        let key_events = events_tx.clone();
+        let input = console.input(&done);
+        s.spawn(move || {
+            for event in input.events() {
+                let event = match event {
+                    Ok(event) => input_event(event),
+                    Err(e) => Some(Event::Error(e)),
+                };
+                let Some(event) = event else {
+                    continue;
+                };
+                let failed = matches!(event, Event::Error(_));
+                if key_events.send(event).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        let lines = &stdin_lines;
+        let generation = &generation;
+        s.spawn(move || {
+            worker::run(lines, window, jobs, generation, |update| {
+                let _ = events_tx.send(Event::Scores(update));
+            })
+        });
+        let result = handle_events(
+            &console,
+            &stdin_lines,
+            events,
+            jobs_tx,
+            generation,
+            config,
+            history,
+        );
+        // stop reading keys and abandon any scoring still in progress so the threads can be joined
+        done.store(true, Ordering::SeqCst);
+        generation.fetch_add(1, Ordering::SeqCst);
+        result
+    })
+}
 
-    if !query.is_empty() {
-        // "prime" the cache with the scores for "", since an initial query was specified
-        let mut scores: Vec<Score> = stdin_lines
-            .par_iter()
-            .filter_map(|l| calculate_score(l, &[]))
-            .collect();
-        scores.sort_unstable_by(Score::cmp);
-        score_map.insert("".to_string(), Rc::new(scores));
-    }
+fn handle_events<'a>(
+    console: &Console,
+    stdin_lines: &'a [Line],
+    events: Receiver<Event<'a>>,
+    jobs: Sender<Job<'a>>,
+    generation: &AtomicUsize,
+    config: &Config<'a>,
+    mut history: Option<&mut History>,
+) -> io::Result<String> {
+    let height = config.ui_height(console);
+    let multiple = config.multiple;
+    let mut query: Vec<char> = config.initial_search.chars().collect();
+    let mut need_new_scores = true;
+    let mut score_map = ScoreCache::new(config.cache_size);
+    // updates for jobs older than this were scored in another mode, so they aren't cached
+    let mut cache_from = 0;
+    // why the query can't be matched against, e.g. an invalid regex
+    let mut error: Option<String> = None;
+    let mut regex = config.regex;
+    let matcher = |regex: bool| {
+        if regex {
+            config.regex_matcher
+        } else {
+            config.matcher
+        }
+    };
+    let mut tagged = Selection::new(config.limit);
+    let mut scores = View::default();
+    let mut sort = config.sort;
+    let order = |sort: bool| {
+        if sort {
+            Order::Score(config.tiebreaks)
+        } else {
+            Order::Input
+        }
+    };
 
-    let mut scores: Vec<Score> = stdin_lines
-        .par_iter()
-        .filter_map(|l| calculate_score(l, &query))
-        .collect();
-    scores.sort_unstable_by(Score::cmp);
-    let mut scores = Rc::new(scores);
-    score_map.insert(query_str(&query), Rc::clone(&scores));
+    let send_job = |query: &[char],
+                    matcher: &'a dyn Matcher,
+                    order: Order,
+                    candidates: Option<Arc<Matches<'a>>>| {
+        let _ = jobs.send(Job {
+            generation: generation.fetch_add(1, Ordering::SeqCst) + 1,
+            query: query.to_vec(),
+            matcher,
+            order,
+            candidates,
+        });
+    };
 
+    let rows = config.rows(height);
     let renderer_config = RendererConfig {
         width: console.width as usize,
-        height: min(height, console.height as usize),
-        fg: &colors.0,
-        bg: &colors.1,
+        height,
+        fg: &config.colors.0,
+        bg: &config.colors.1,
         match_count_length: format!("{}", stdin_lines.len()).len(),
+        total: stdin_lines.len(),
+        header: visible_header(config.header, rows),
+        prompt: config.prompt,
+        pointer: config.pointer,
+        marker: config.marker,
+        counter: config.counter,
+        border: config.border,
+        margin: config.margin,
+        padding: config.padding,
     };
 
-    let render = |scores: Rc<Vec<Score>>, query: &[char], selected: usize, tagged: &[usize]| {
-        console.write(
-            &Renderer::new(
-                &renderer_config,
-                scores,
-                query_str(query),
-                selected,
-                tagged,
-            )
-            .render(),
+    let render = |scores: &View,
+                  query: &[char],
+                  selected: usize,
+                  offset: usize,
+                  tagged: &Selection,
+                  status: Option<&str>,
+                  scanning: bool,
+                  matcher: &'a dyn Matcher| {
+        let renderer = Renderer::new(
+            &renderer_config,
+            scores,
+            query_str(query),
+            selected,
+            offset,
+            tagged,
+            status,
         )
+        .scanning(scanning)
+        .matcher(matcher);
+        console.draw(renderer.render(), renderer.cursor_row())
     };
 
-    let num_visible = |scores: &[Score]| min(renderer_config.height - 1, scores.len());
+    let window = rows - renderer_config.header.len();
     let mut selected = 0;
-    render(Rc::clone(&scores), &query, selected, &tagged);
+    let mut offset = 0;
+    let mut need_render = true;
+    // whether the worker has published partial scores for the current query and is still going
+    let mut scanning = false;
+    // whether the matches shown may not be the current query's, until the worker finishes it
+    let mut pending = false;
+    // enter (or alt-enter) pressed while the query was pending, which is handled once it isn't
+    let mut accepting: Option<Key> = None;
+    // the match last clicked, and when
+    let mut last_click: Option<(usize, Instant)> = None;
 
-    for c in tty.keys() {
-        match c.unwrap() {
-            Key::Ctrl('c') | Key::Esc => {
-                console.write(&Renderer::clear());
-                return Err(io::Error::new(io::ErrorKind::Other, ""));
+    loop {
+        if need_new_scores {
+            need_new_scores = false;
+            error = matcher(regex).validate(&query).err();
+            let candidates = match error {
+                // an invalid query matches nothing
+                Some(_) => Some(Arc::new(Matches::default())),
+                None => find_candidates(&mut score_map, matcher(regex), &query),
+            };
+            match candidates {
+                // nothing needs scoring if the query was already scored, if a prefix of it
+                // matched nothing at all, or if it's invalid
+                Some(cached) if cached.is_empty() || score_map.contains_key(&query_str(&query)) => {
+                    // make sure any job still running for an older query is abandoned
+                    generation.fetch_add(1, Ordering::SeqCst);
+                    scores = View::new(cached, order(sort));
+                    scanning = false;
+                    pending = false;
+                }
+                candidates => {
+                    send_job(&query, matcher(regex), order(sort), candidates);
+                    pending = true;
+                }
             }
-            Key::Char('\n') => {
-                console.write(&Renderer::clear());
-                if scores.is_empty() {
-                    return Ok(String::new());
+            need_render = true;
+        }
+
+        if need_render {
+            need_render = false;
+            selected = min(selected, scores.len().saturating_sub(1));
+            offset = scroll_offset(offset, selected, window);
+            scores.sort_to(offset + window);
+            let status = match &error {
+                Some(e) if regex => Some(format!("invalid regex: {}", e)),
+                Some(e) => Some(e.clone()),
+                None if regex => Some("regex".to_string()),
+                None => None,
+            };
+            render(
+                &scores,
+                &query,
+                selected,
+                offset,
+                &tagged,
+                status.as_deref(),
+                scanning,
+                matcher(regex),
+            );
+        }
+
+        // the key reader and the worker both hold senders for as long as this runs. events other
+        // than key presses are handled here, unless they amount to one.
+        let event = match accepting {
+            Some(key) if !pending => {
+                accepting = None;
+                Event::Key(key)
+            }
+            _ => events.recv().unwrap(),
+        };
+        // once enter has been pressed, nothing but cancelling changes what's accepted
+        let cancels = matches!(event, Event::Key(Key::Ctrl('c') | Key::Esc));
+        if accepting.is_some() && !cancels && !matches!(event, Event::Scores(_) | Event::Error(_)) {
+            continue;
+        }
+        let key = match event {
+            Event::Scores(update) => {
+                if update.done && update.generation >= cache_from {
+                    score_map.insert(query_str(&update.query), Arc::clone(&update.scores));
                 }
-                return Ok(scores[selected].line.buf.clone());
+                if update.generation == generation.load(Ordering::SeqCst) {
+                    // the order may have been toggled while the scores were being calculated
+                    scores = View::new(update.scores, order(sort));
+                    scanning = !update.done;
+                    pending = !update.done;
+                    need_render = true;
+                }
+                continue;
+            }
+            Event::Error(e) => {
+                console.clear();
+                return Err(e);
             }
-            Key::Alt('\r') => {
-                console.write(&Renderer::clear());
+            Event::Shift(key) => {
+                // tag the selected line and move, so that holding shift tags a range of lines
+                if let Some(score) = scores.get(selected).filter(|_| multiple) {
+                    tagged.tag(score.line.index);
+                    match key {
+                        Key::Up => selected = selected.saturating_sub(1),
+                        _ => selected = min(selected + 1, scores.len() - 1),
+                    }
+                    need_render = true;
+                }
+                continue;
+            }
+            Event::Mouse(MouseEvent::Press(button, _, y)) => match button {
+                MouseButton::WheelUp | MouseButton::WheelDown => {
+                    // scroll by moving the selection, without wrapping around
+                    selected = match button {
+                        MouseButton::WheelUp => selected.saturating_sub(1),
+                        _ => min(selected + 1, scores.len().saturating_sub(1)),
+                    };
+                    need_render = true;
+                    continue;
+                }
+                MouseButton::Left => {
+                    // select the clicked line. clicking it again soon after accepts it
+                    let framing = framing_rows(config.border, config.margin, config.padding);
+                    let first_row =
+                        framing / 2 + 1 + config.counter.rows() + renderer_config.header.len();
+                    let clicked = console
+                        .frame_row(y)
+                        .and_then(|row| row.checked_sub(first_row))
+                        .filter(|&row| row < window)
+                        .map(|row| offset + row)
+                        .filter(|&i| i < scores.len());
+                    let Some(clicked) = clicked else {
+                        continue;
+                    };
+                    let now = Instant::now();
+                    let double_click = last_click.is_some_and(|(i, at)| {
+                        i == clicked && now.duration_since(at) < DOUBLE_CLICK_INTERVAL
+                    });
+                    selected = clicked;
+                    need_render = true;
+                    if !double_click {
+                        last_click = Some((clicked, now));
+                        continue;
+                    }
+                    Key::Char('\n')
+                }
+                _ => continue,
+            },
+            Event::Mouse(_) => continue,
+            Event::Key(key) => key,
+        };
+        match key {
+            Key::Ctrl('c') | Key::Esc => {
+                console.clear();
+                return Err(io::Error::other(""));
+            }
+            Key::Char('\n') | Key::Alt('\r') if pending => {
+                // the matches shown may be for an earlier query, so wait for the current one's
+                accepting = Some(key);
+            }
+            Key::Char('\n') | Key::Alt('\r') => {
+                console.clear();
+                if let Some(history) = history {
+                    history.add(&query_str(&query));
+                }
+                // alt-enter always accepts the tagged lines, enter only if configured to
+                let accept_tagged = key == Key::Alt('\r') || config.accept_tagged;
+                if multiple && accept_tagged && !tagged.is_empty() {
+                    if config.tagged_order == TaggedOrder::Score {
+                        scores.sort_to(scores.len());
+                    }
+                    let ranking = scores.iter().map(|s| s.line.index);
+                    return Ok(tagged
+                        .ordered(config.tagged_order, ranking)
+                        .iter()
+                        .map(|i| stdin_lines[*i].buf.clone())
+                        .collect::<Vec<String>>()
+                        .join("\n"));
+                }
                 if scores.is_empty() {
                     return Ok(String::new());
                 }
-                if tagged.is_empty() || !multiple {
-                    return Ok(scores[selected].line.buf.clone());
-                }
-                return Ok(tagged
-                    .iter()
-                    .map(|i| stdin_lines[*i].buf.clone())
-                    .collect::<Vec<String>>()
-                    .join("\n"));
+                return Ok(scores[selected].line.buf.clone());
             }
             Key::Char('\t') => {
                 if multiple {
-                    let line = scores[selected].line;
-                    if tagged.contains(&line.index) {
-                        if let Ok(index) = tagged.binary_search(&line.index) {
-                            tagged.remove(index);
-                        }
-                    } else {
-                        tagged.push(line.index);
+                    if let Some(score) = scores.get(selected) {
+                        tagged.toggle(score.line.index);
+                        need_render = true;
                     }
-                    render(Rc::clone(&scores), &query, selected, &tagged);
                 } else {
                     query.push('\t');
                     need_new_scores = true;
                 }
             }
+            Key::Ctrl('a') | Key::Ctrl('d') | Key::Ctrl('t') if multiple => {
+                // tag, untag or invert every match. the lines are tagged in the order they're
+                // shown if that's the order they're output in, or if only so many can be tagged.
+                // otherwise, as when untagging, the order doesn't matter.
+                let in_order = key != Key::Ctrl('d')
+                    && (config.tagged_order == TaggedOrder::Tag || config.limit.is_some());
+                if in_order && sort {
+                    scores.sort_to(scores.len());
+                }
+                let mut indices: Vec<usize> = scores.iter().map(|s| s.line.index).collect();
+                if in_order && !sort {
+                    // in input order, which is the order of the lines' indices
+                    indices.sort_unstable();
+                }
+                match key {
+                    Key::Ctrl('a') => tagged.tag_all(indices),
+                    Key::Ctrl('d') => tagged.untag_all(indices),
+                    _ => tagged.invert(indices),
+                }
+                need_render = true;
+            }
+            Key::Ctrl('p') | Key::Ctrl('n') if history.is_some() => {
+                // recall queries instead of moving the selection
+                let history = history.as_deref_mut().unwrap();
+                let recalled = match key {
+                    Key::Ctrl('p') => history.previous(&query_str(&query)),
+                    _ => history.next(),
+                };
+                if let Some(recalled) = recalled {
+                    query = recalled.chars().collect();
+                    need_new_scores = true;
+                }
+            }
             Key::Ctrl('n') | Key::Down => {
-                // move selection down
-                if selected < num_visible(&scores) - 1 {
+                // move selection down, wrapping around to the top
+                if selected + 1 < scores.len() {
                     selected += 1;
-                    render(Rc::clone(&scores), &query, selected, &tagged);
+                    need_render = true;
                 } else if selected > 0 {
                     selected = 0;
-                    render(Rc::clone(&scores), &query, selected, &tagged);
+                    need_render = true;
                 }
             }
             Key::Ctrl('p') | Key::Up => {
-                // move selection up
+                // move selection up, wrapping around to the bottom
                 if selected > 0 {
                     selected -= 1;
-                    render(Rc::clone(&scores), &query, selected, &tagged);
-                } else if num_visible(&scores) > 0 {
-                    selected = num_visible(&scores) - 1;
-                    render(Rc::clone(&scores), &query, selected, &tagged);
+                    need_render = true;
+                } else if scores.len() > 1 {
+                    selected = scores.len() - 1;
+                    need_render = true;
                 }
             }
+            Key::Ctrl('s') => {
+                // toggle between ordering by score and input order
+                sort = !sort;
+                scores = View::new(Arc::clone(scores.matches()), order(sort));
+                selected = 0;
+                need_render = true;
+            }
+            Key::Ctrl('r') => {
+                // toggle regex mode. cached scores only apply to the mode they were
+                // calculated in, so they're all forgotten, along with any being calculated
+                regex = !regex;
+                score_map = ScoreCache::new(config.cache_size);
+                cache_from = generation.load(Ordering::SeqCst) + 1;
+                need_new_scores = true;
+            }
             Key::Ctrl('w') => {
                 // delete word
                 need_new_scores = !query.is_empty();
@@ -179,9 +562,7 @@ pub fn run(
                 query.clear();
             }
             Key::Backspace => {
-                if query.pop().is_some() {
-                    need_new_scores = true;
-                }
+                need_new_scores = query.pop().is_some();
             }
             Key::Char(c) => {
                 query.push(c);
@@ -189,23 +570,13 @@ pub fn run(
             }
             _ => {}
         }
-
-        if need_new_scores {
-            need_new_scores = false;
-            scores = get_scores(&mut score_map, &query);
-            if scores.len() > 0 {
-                selected = min(selected, scores.len() - 1);
-            }
-            render(Rc::clone(&scores), &query, selected, &tagged);
-        }
     }
-
-    unreachable!();
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use star::Selecta;
 
     #[test]
     fn test_query_str() {
@@ -213,44 +584,130 @@ mod tests {
     }
 
     #[test]
-    fn test_get_scores() {
-        let lines = vec![Line::from("foo"), Line::from("bar"), Line::from("baz")];
+    fn test_input_event() {
+        let key = |event| match input_event(event) {
+            Some(Event::Key(key)) => Some((false, key)),
+            Some(Event::Shift(key)) => Some((true, key)),
+            _ => None,
+        };
+        assert_eq!(key(TermEvent::Key(Key::Up)), Some((false, Key::Up)));
+        let shifted = |seq: &[u8]| key(TermEvent::Unsupported(seq.to_vec()));
+        assert_eq!(shifted(b"\x1b[1;2A"), Some((true, Key::Up)));
+        assert_eq!(shifted(b"\x1b[1;2B"), Some((true, Key::Down)));
+        assert_eq!(shifted(b"\x1b[1;5A"), None);
+        let click = MouseEvent::Press(MouseButton::Left, 1, 2);
+        let event = input_event(TermEvent::Mouse(click));
+        assert!(matches!(event, Some(Event::Mouse(m)) if m == click));
+    }
+
+    #[test]
+    fn test_visible_header() {
+        let header: Vec<String> = ["a", "b", "c"].iter().map(|h| h.to_string()).collect();
+        assert_eq!(visible_header(&header, 9), &header[..]);
+        assert_eq!(visible_header(&header, 3), &header[..2]);
+        assert!(visible_header(&header, 0).is_empty());
+    }
+
+    #[test]
+    fn test_scroll_offset() {
+        // selection within the window doesn't scroll
+        assert_eq!(scroll_offset(0, 4, 5), 0);
+        assert_eq!(scroll_offset(3, 3, 5), 3);
+        // selection past the bottom of the window scrolls down just enough
+        assert_eq!(scroll_offset(0, 5, 5), 1);
+        assert_eq!(scroll_offset(0, 12, 5), 8);
+        // selection above the top of the window scrolls up to it
+        assert_eq!(scroll_offset(8, 2, 5), 2);
+    }
+
+    #[test]
+    fn test_find_candidates() {
+        let matcher = Selecta::default();
+        let lines = [
+            Line::new("foo".to_string(), 0),
+            Line::new("bar".to_string(), 1),
+            Line::new("baz".to_string(), 2),
+        ];
         let mut query: Vec<char> = vec![];
-        let mut map: HashMap<String, Rc<Vec<Score>>> = HashMap::new();
-        let scores = Rc::new(
+        let mut map = ScoreCache::new(usize::MAX);
+        // nothing has been scored yet, so every line is a candidate
+        assert_eq!(find_candidates(&mut map, &matcher, &query), None);
+        let scores = Arc::new(
             lines
                 .iter()
-                .filter_map(|l| calculate_score(l, &query))
+                .filter_map(|l| Selecta::default().score(l, &query))
                 .collect(),
         );
-        map.insert("".to_string(), Rc::clone(&scores));
-        // we should get new scores that were calculated from the "" scores
+        map.insert("".to_string(), Arc::clone(&scores));
+        // we should get the "" scores as candidates for the "b" query
         query.push('b');
-        let result = get_scores(&mut map, &query);
-        assert_eq!(result.len(), 2);
-        assert_ne!(result, scores);
+        assert_eq!(
+            find_candidates(&mut map, &matcher, &query),
+            Some(Arc::clone(&scores))
+        );
         // create new scores to associate with the "b" query
-        let scores: Rc<Vec<Score>> = Rc::new(
+        let scores: Arc<Matches> = Arc::new(
             lines
                 .iter()
-                .filter_map(|l| calculate_score(l, &query))
+                .filter_map(|l| Selecta::default().score(l, &query))
                 .collect(),
         );
         // there should only be two scores
         assert_eq!(scores.len(), 2);
-        map.insert(query_str(&query), Rc::clone(&scores));
+        map.insert(query_str(&query), Arc::clone(&scores));
         // just make sure we got those scores back with no change in query
-        let result = get_scores(&mut map, &query);
-        assert_eq!(result, scores);
-        // now add a char to the query
+        assert_eq!(
+            find_candidates(&mut map, &matcher, &query),
+            Some(Arc::clone(&scores))
+        );
+        // now add chars to the query - the "b" scores are the smallest cached candidates
         query.push('a');
-        // we should get new scores that were calculated from the "b" scores
-        let result = get_scores(&mut map, &query);
-        assert_eq!(result.len(), 2);
-        assert_ne!(result, scores);
-        // we should get scores we already calculated, if they exist
-        let old_result = result.clone();
-        let result = get_scores(&mut map, &query);
-        assert_eq!(result, old_result);
+        query.push('z');
+        assert_eq!(
+            find_candidates(&mut map, &matcher, &query),
+            Some(Arc::clone(&scores))
+        );
+        // a query that doesn't share a cached prefix other than "" gets the "" scores
+        let result = find_candidates(&mut map, &matcher, &['f']).unwrap();
+        assert_eq!(result.len(), 3);
+    }
+
+    #[test]
+    fn test_find_candidates_subsequence() {
+        let matcher = Selecta::default();
+        let lines = [
+            Line::new("foobar".to_string(), 0),
+            Line::new("foobaz".to_string(), 1),
+            Line::new("fizz".to_string(), 2),
+            Line::new("bar".to_string(), 3),
+        ];
+        let mut map = ScoreCache::new(usize::MAX);
+        let scores = |query: &[char]| -> Arc<Matches> {
+            Arc::new(
+                lines
+                    .iter()
+                    .filter_map(|l| Selecta::default().score(l, query))
+                    .collect(),
+            )
+        };
+        for query in [vec![], vec!['f'], vec!['f', 'z'], vec!['b', 'z']] {
+            map.insert(query_str(&query), scores(&query));
+        }
+        // "fz" and "bz" are both subsequences of "fbz", but "bz" matches fewer lines
+        let result = find_candidates(&mut map, &matcher, &['f', 'b', 'z']).unwrap();
+        assert_eq!(result, scores(&['b', 'z']));
+        assert_eq!(result.len(), 1);
+        // deleting a character from the middle of "fbz" gets the exact scores for "fz"
+        let result = find_candidates(&mut map, &matcher, &['f', 'z']).unwrap();
+        assert_eq!(result, scores(&['f', 'z']));
+        // "f" is the smallest subsequence of "zf", even though it isn't a prefix
+        let result = find_candidates(&mut map, &matcher, &['z', 'f']).unwrap();
+        assert_eq!(result, scores(&['f']));
+        // case is ignored when comparing queries
+        let result = find_candidates(&mut map, &matcher, &['F', 'I', 'Z']).unwrap();
+        assert_eq!(result, scores(&['f', 'z']));
+        // with nothing but "" in common, every line is a candidate
+        let result = find_candidates(&mut map, &matcher, &['q']).unwrap();
+        assert_eq!(result.len(), 4);
     }
 }
@@ -0,0 +1,351 @@
+use star::{Line, Matcher, Score};
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{self, Error, ErrorKind},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+// once the ranks of all the entries add up to more than this, they're all scaled down so that old
+// selections are gradually forgotten
+const MAX_TOTAL_RANK: f64 = 1000.0;
+const AGING_FACTOR: f64 = 0.9;
+// the highest bonus a frecent line can get. bonuses only break ties between lines given the same
+// points, so this is how many levels of frecency are told apart
+const MAX_BONUS: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Entry {
+    rank: f64,
+    last_used: u64,
+}
+
+impl Entry {
+    // selections count for more the more recently they were made
+    fn frecency(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last_used);
+        let weight = if age < HOUR {
+            4.0
+        } else if age < DAY {
+            2.0
+        } else if age < WEEK {
+            0.5
+        } else {
+            0.25
+        };
+        self.rank * weight
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn data_dir() -> Option<PathBuf> {
+    env::var_os("XDG_DATA_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/share")))
+        .map(|d| d.join("star"))
+}
+
+// each line of the database is the rank, the time of the last selection and the selected line,
+// separated by tabs
+fn parse_entry(s: &str) -> Option<(String, Entry)> {
+    let mut fields = s.splitn(3, '\t');
+    let rank = fields.next()?.parse().ok()?;
+    let last_used = fields.next()?.parse().ok()?;
+    let line = fields.next()?;
+    Some((line.to_string(), Entry { rank, last_used }))
+}
+
+// the lines selected in one context (e.g. "files" or "branches"), remembering how often and how
+// recently each was selected
+pub struct Frecency {
+    path: PathBuf,
+    entries: HashMap<String, Entry>,
+}
+
+impl Frecency {
+    pub fn open(key: &str) -> io::Result<Self> {
+        if key.is_empty()
+            || key.starts_with('.')
+            || !key
+                .chars()
+                .all(|c| c.is_alphanumeric() || "-_.".contains(c))
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid history key: \"{}\"", key),
+            ));
+        }
+        let dir = data_dir().ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                "couldn't find a directory for the history database",
+            )
+        })?;
+        Self::load(dir.join("frecency").join(key))
+    }
+
+    fn load(path: PathBuf) -> io::Result<Self> {
+        let contents = match fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(Self {
+            path,
+            entries: contents.lines().filter_map(parse_entry).collect(),
+        })
+    }
+
+    pub fn bonus(&self, line: &str, now: u64) -> usize {
+        self.entries.get(line).map_or(0, |entry| {
+            // every doubling of frecency is worth another point
+            ((1.0 + entry.frecency(now)).log2() as usize).min(MAX_BONUS)
+        })
+    }
+
+    pub fn record(&mut self, line: &str, now: u64) {
+        let entry = self.entries.entry(line.to_string()).or_insert(Entry {
+            rank: 0.0,
+            last_used: now,
+        });
+        entry.rank += 1.0;
+        entry.last_used = now;
+        if self.entries.values().map(|e| e.rank).sum::<f64>() > MAX_TOTAL_RANK {
+            for entry in self.entries.values_mut() {
+                entry.rank *= AGING_FACTOR;
+            }
+            self.entries.retain(|_, e| e.rank >= 1.0);
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents: String = self
+            .entries
+            .iter()
+            .map(|(line, e)| format!("{}\t{}\t{}\n", e.rank, e.last_used, line))
+            .collect();
+        // write to a temporary file first so that the database is never left half-written
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, contents)?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+// breaks ties between the lines another matcher gives the same points with their frecency bonus,
+// so that lines selected often and recently rank above equally good matches. every matcher's
+// points mean something different, so the bonus can't simply be taken off them; instead they're
+// scaled up to leave room for it below each of them.
+pub struct FrecentMatcher<'a> {
+    matcher: &'a dyn Matcher,
+    // indexed by `Line::index`
+    bonuses: Vec<usize>,
+}
+
+impl<'a> FrecentMatcher<'a> {
+    pub fn new(matcher: &'a dyn Matcher, lines: &[Line], frecency: &Frecency, now: u64) -> Self {
+        let mut bonuses = vec![0; lines.iter().map(|l| l.index + 1).max().unwrap_or(0)];
+        for line in lines {
+            bonuses[line.index] = frecency.bonus(&line.buf, now);
+        }
+        Self { matcher, bonuses }
+    }
+}
+
+impl Matcher for FrecentMatcher<'_> {
+    fn score<'a>(&self, line: &'a Line, query: &[char]) -> Option<Score<'a>> {
+        let bonus = self.bonuses.get(line.index).copied().unwrap_or(0);
+        self.matcher.score(line, query).map(|s| Score {
+            // the only points too many to scale are the most there can be, given to every line for
+            // an empty query, which taking the bonus off breaks ties between just the same
+            points: match s.points.checked_mul(MAX_BONUS + 1) {
+                Some(points) => points + MAX_BONUS - bonus,
+                None => s.points - bonus,
+            },
+            ..s
+        })
+    }
+
+    fn positions(&self, line: &Line, query: &[char]) -> Option<Vec<usize>> {
+        self.matcher.positions(line, query)
+    }
+
+    fn validate(&self, query: &[char]) -> Result<(), String> {
+        self.matcher.validate(query)
+    }
+
+    fn narrows(&self, query: &str, other: &str) -> bool {
+        self.matcher.narrows(query, other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use star::{Selecta, Substring};
+
+    fn frecency() -> Frecency {
+        Frecency {
+            path: PathBuf::new(),
+            entries: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_entry() {
+        let entry = Entry {
+            rank: 2.5,
+            last_used: 100,
+        };
+        assert_eq!(
+            parse_entry("2.5\t100\tsrc/main.rs"),
+            Some(("src/main.rs".to_string(), entry))
+        );
+        // the line itself can contain tabs
+        assert_eq!(
+            parse_entry("2.5\t100\ta\tb"),
+            Some(("a\tb".to_string(), entry))
+        );
+        assert_eq!(parse_entry("2.5\t100"), None);
+        assert_eq!(parse_entry("x\t100\tfoo"), None);
+    }
+
+    #[test]
+    fn test_bonus() {
+        let mut f = frecency();
+        assert_eq!(f.bonus("foo", 0), 0);
+        f.record("foo", 0);
+        // selected once, just now
+        assert_eq!(f.bonus("foo", 0), 2);
+        // the same selection counts for less as it ages
+        assert_eq!(f.bonus("foo", DAY), 0);
+        // and more the more often it's made, up to a limit
+        for _ in 0..20 {
+            f.record("foo", 0);
+        }
+        assert_eq!(f.bonus("foo", 0), MAX_BONUS);
+        assert_eq!(f.bonus("bar", 0), 0);
+    }
+
+    #[test]
+    fn test_aging() {
+        let mut f = frecency();
+        f.record("rare", 0);
+        for _ in 0..MAX_TOTAL_RANK as usize {
+            f.record("common", 0);
+        }
+        // going over the total scaled everything down, forgetting the rarely selected line
+        assert!(f.entries["common"].rank < MAX_TOTAL_RANK);
+        assert!(!f.entries.contains_key("rare"));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = env::temp_dir().join(format!("star-frecency-test-{}", std::process::id()));
+        let mut f = frecency();
+        f.path = dir.join("files");
+        f.record("src/main.rs", 100);
+        f.record("a\tb", 100);
+        f.save().unwrap();
+        // a key with a dot in it doesn't share its temporary file with the key before the dot
+        let mut g = frecency();
+        g.path = dir.join("files.v2");
+        g.record("README.md", 100);
+        g.save().unwrap();
+        let loaded = Frecency::load(dir.join("files")).unwrap();
+        assert_eq!(loaded.entries, f.entries);
+        assert_eq!(
+            Frecency::load(dir.join("files.v2")).unwrap().entries,
+            g.entries
+        );
+        // a missing database is just empty
+        assert!(Frecency::load(dir.join("missing"))
+            .unwrap()
+            .entries
+            .is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_open_invalid_key() {
+        assert!(Frecency::open("").is_err());
+        assert!(Frecency::open("../files").is_err());
+        assert!(Frecency::open(".hidden").is_err());
+    }
+
+    #[test]
+    fn test_frecent_matcher() {
+        let lines = [
+            Line::new("foo_bar".to_string(), 0),
+            Line::new("foo_baz".to_string(), 1),
+            Line::new("for".to_string(), 2),
+        ];
+        let mut f = frecency();
+        f.record("foo_baz", 0);
+        f.record("foo_bar", 0);
+        f.record("foo_bar", 0);
+        let selecta = Selecta::default();
+        let matcher = FrecentMatcher::new(&selecta, &lines, &f, 0);
+        let ranked = |query: &str| -> Vec<String> {
+            matcher
+                .rank(&lines, query)
+                .iter()
+                .map(|s| s.line.buf.clone())
+                .collect()
+        };
+        // equally good matches are ordered by frecency
+        assert_eq!(ranked("fba"), vec!["foo_bar", "foo_baz"]);
+        // but a much better match still wins
+        assert_eq!(ranked("for"), vec!["for", "foo_bar"]);
+        assert_eq!(
+            matcher.positions(&lines[0], &['f', 'b']),
+            selecta.positions(&lines[0], &['f', 'b'])
+        );
+        // so is every line for an empty query
+        assert_eq!(ranked(""), vec!["foo_bar", "foo_baz", "for"]);
+    }
+
+    #[test]
+    fn test_frecent_matcher_ties_only() {
+        let lines = [
+            Line::new("foo".to_string(), 0),
+            Line::new("xfoo".to_string(), 1),
+            Line::new("fxoxo".to_string(), 2),
+        ];
+        let mut f = frecency();
+        for _ in 0..20 {
+            f.record("fxoxo", 0);
+        }
+        f.record("xfoo", 0);
+        // the bonus doesn't outweigh a single point, however few points a matcher gives
+        let selecta = Selecta::default();
+        let matcher = FrecentMatcher::new(&selecta, &lines, &f, 0);
+        let ranked: Vec<usize> = matcher
+            .rank(&lines, "foo")
+            .iter()
+            .map(|s| s.line.index)
+            .collect();
+        assert_eq!(ranked, vec![1, 0, 2]);
+        // but it still orders matchers' ties, even where every match gets the same points
+        let matcher = FrecentMatcher::new(&Substring, &lines, &f, 0);
+        let ranked: Vec<usize> = matcher
+            .rank(&lines, "foo")
+            .iter()
+            .map(|s| s.line.index)
+            .collect();
+        assert_eq!(ranked, vec![1, 0]);
+    }
+}
@@ -0,0 +1,315 @@
+use crate::{line::Line, matcher::Matcher, score::Score};
+use std::cmp::Ordering;
+
+// the scoring scheme of fzf's v2 algorithm. matched characters earn points, bonus points are
+// awarded for matching at word boundaries, camelCase transitions and in consecutive runs, and gaps
+// between matched characters cost points.
+const SCORE_MATCH: isize = 16;
+const SCORE_GAP_START: isize = -3;
+const SCORE_GAP_EXTENSION: isize = -1;
+const BONUS_BOUNDARY: isize = SCORE_MATCH / 2;
+const BONUS_NON_WORD: isize = SCORE_MATCH / 2;
+const BONUS_CAMEL_123: isize = BONUS_BOUNDARY + SCORE_GAP_EXTENSION;
+const BONUS_CONSECUTIVE: isize = -(SCORE_GAP_START + SCORE_GAP_EXTENSION);
+const BONUS_FIRST_CHAR_MULTIPLIER: isize = 2;
+// no character can earn more than this, so it's used to turn scores into penalty points
+const MAX_SCORE_PER_CHAR: isize = SCORE_MATCH + BONUS_BOUNDARY * BONUS_FIRST_CHAR_MULTIPLIER;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CharClass {
+    Lower,
+    Upper,
+    Number,
+    NonWord,
+}
+
+fn char_class(c: char) -> CharClass {
+    if c.is_lowercase() {
+        CharClass::Lower
+    } else if c.is_uppercase() {
+        CharClass::Upper
+    } else if c.is_numeric() {
+        CharClass::Number
+    } else if c.is_alphabetic() {
+        // letters without case, e.g. CJK
+        CharClass::Lower
+    } else {
+        CharClass::NonWord
+    }
+}
+
+fn bonus(prev: CharClass, class: CharClass) -> isize {
+    match (prev, class) {
+        (CharClass::NonWord, CharClass::NonWord) => BONUS_NON_WORD,
+        (CharClass::NonWord, _) => BONUS_BOUNDARY,
+        (CharClass::Lower, CharClass::Upper) => BONUS_CAMEL_123,
+        (CharClass::Number, CharClass::Number) => 0,
+        (_, CharClass::Number) => BONUS_CAMEL_123,
+        (_, CharClass::NonWord) => BONUS_NON_WORD,
+        _ => 0,
+    }
+}
+
+fn lowercase(c: char) -> char {
+    // one char in, one char out, so that positions in the lowercased line match the original
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+// the best alignment of the query so far that ends with a match at a given position
+#[derive(Clone, Copy, Debug)]
+struct Cell {
+    score: isize,
+    first: usize,
+    // the bonus of the first character in the run of consecutive matches ending here
+    run_bonus: isize,
+    // where the previous query character was matched
+    prev: usize,
+}
+
+/// An optimal aligner in the style of fzf's v2 algorithm (a variant of Smith-Waterman). Every
+/// alignment of the query against the line is considered, and the one earning the most points,
+/// with bonuses for word boundaries, camelCase and consecutive characters, wins. Slower than
+/// Selecta, but better at ranking.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Fzf;
+
+impl Matcher for Fzf {
+    fn score<'a>(&self, line: &'a Line, query: &[char]) -> Option<Score<'a>> {
+        if query.is_empty() {
+            return Some(Score::new(line));
+        }
+        let (last, cell) = align(line, query, None)?;
+        Some(Score {
+            first: cell.first,
+            last: last + 1,
+            points: (MAX_SCORE_PER_CHAR * query.len() as isize - cell.score) as usize,
+            line,
+        })
+    }
+
+    fn positions(&self, line: &Line, query: &[char]) -> Option<Vec<usize>> {
+        if query.is_empty() {
+            return Some(vec![]);
+        }
+        let mut history = vec![];
+        let (last, mut cell) = align(line, query, Some(&mut history))?;
+        let mut positions = vec![last];
+        for row in history.iter().rev().skip(1) {
+            positions.push(cell.prev);
+            cell = row[cell.prev].unwrap();
+        }
+        positions.reverse();
+        Some(positions)
+    }
+}
+
+// returns the position of the last matched character of the best alignment and the cell that
+// matched it. if `history` is given, every row of cells is pushed to it so that the alignment can
+// be traced back.
+fn align(
+    line: &Line,
+    query: &[char],
+    mut history: Option<&mut Vec<Vec<Option<Cell>>>>,
+) -> Option<(usize, Cell)> {
+    let query: Vec<char> = query.iter().map(|c| lowercase(*c)).collect();
+    let chars: Vec<char> = line.buf.chars().collect();
+    let lower: Vec<char> = chars.iter().map(|c| lowercase(*c)).collect();
+
+    // bail out early if the query isn't a subsequence of the line at all
+    let mut rest = lower.iter();
+    if !query.iter().all(|q| rest.any(|c| c == q)) {
+        return None;
+    }
+
+    let mut prev_class = CharClass::NonWord;
+    let bonuses: Vec<isize> = chars
+        .iter()
+        .map(|c| {
+            let class = char_class(*c);
+            let b = bonus(prev_class, class);
+            prev_class = class;
+            b
+        })
+        .collect();
+
+    let mut row: Vec<Option<Cell>> = lower
+        .iter()
+        .enumerate()
+        .map(|(j, c)| {
+            (*c == query[0]).then(|| Cell {
+                score: SCORE_MATCH + bonuses[j] * BONUS_FIRST_CHAR_MULTIPLIER,
+                first: j,
+                run_bonus: bonuses[j],
+                prev: j,
+            })
+        })
+        .collect();
+
+    for q in &query[1..] {
+        let mut next: Vec<Option<Cell>> = vec![None; lower.len()];
+        // the best alignment of the previous query characters that leaves a gap before j
+        let mut gap: Option<Cell> = None;
+        for j in 1..lower.len() {
+            if j >= 2 {
+                gap = best(
+                    gap.map(|g| Cell {
+                        score: g.score + SCORE_GAP_EXTENSION,
+                        ..g
+                    }),
+                    row[j - 2].map(|p| Cell {
+                        score: p.score + SCORE_GAP_START,
+                        prev: j - 2,
+                        ..p
+                    }),
+                );
+            }
+            if lower[j] != *q {
+                continue;
+            }
+            let consecutive = row[j - 1].map(|p| {
+                let b = bonuses[j].max(p.run_bonus).max(BONUS_CONSECUTIVE);
+                Cell {
+                    score: p.score + SCORE_MATCH + b,
+                    first: p.first,
+                    run_bonus: if bonuses[j] >= BONUS_BOUNDARY && bonuses[j] > p.run_bonus {
+                        bonuses[j]
+                    } else {
+                        p.run_bonus
+                    },
+                    prev: j - 1,
+                }
+            });
+            let after_gap = gap.map(|g| Cell {
+                score: g.score + SCORE_MATCH + bonuses[j],
+                first: g.first,
+                run_bonus: bonuses[j],
+                prev: g.prev,
+            });
+            next[j] = best(consecutive, after_gap);
+        }
+        if let Some(history) = history.as_mut() {
+            history.push(row);
+        }
+        row = next;
+    }
+
+    let aligned = row
+        .iter()
+        .enumerate()
+        .filter_map(|(j, cell)| cell.map(|c| (j, c)))
+        .max_by(|(a_last, a), (b_last, b)| {
+            a.score
+                .cmp(&b.score)
+                // prefer the shortest alignment among equal scores
+                .then_with(|| (b_last - b.first).cmp(&(a_last - a.first)))
+                .then_with(|| b_last.cmp(a_last))
+        });
+    if let Some(history) = history {
+        history.push(row);
+    }
+    aligned
+}
+
+fn best(a: Option<Cell>, b: Option<Cell>) -> Option<Cell> {
+    match (a, b) {
+        (Some(a), Some(b)) => match a.score.cmp(&b.score) {
+            Ordering::Less => Some(b),
+            _ => Some(a),
+        },
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranked(lines: &[&str], query: &str) -> Vec<String> {
+        let lines: Vec<Line> = lines.iter().map(|l| Line::from(l)).collect();
+        Fzf.rank(&lines, query)
+            .iter()
+            .map(|s| s.line.buf.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_char_class() {
+        assert_eq!(char_class('a'), CharClass::Lower);
+        assert_eq!(char_class('A'), CharClass::Upper);
+        assert_eq!(char_class('7'), CharClass::Number);
+        assert_eq!(char_class('/'), CharClass::NonWord);
+        assert_eq!(char_class(' '), CharClass::NonWord);
+    }
+
+    #[test]
+    fn test_bonus() {
+        assert_eq!(bonus(CharClass::NonWord, CharClass::Lower), BONUS_BOUNDARY);
+        assert_eq!(bonus(CharClass::Lower, CharClass::Upper), BONUS_CAMEL_123);
+        assert_eq!(bonus(CharClass::Lower, CharClass::Number), BONUS_CAMEL_123);
+        assert_eq!(bonus(CharClass::Number, CharClass::Number), 0);
+        assert_eq!(bonus(CharClass::Lower, CharClass::NonWord), BONUS_NON_WORD);
+        assert_eq!(bonus(CharClass::Lower, CharClass::Lower), 0);
+    }
+
+    #[test]
+    fn test_score() {
+        assert_eq!(Fzf.score(&Line::from("foo"), &['q', 'x', 'z']), None);
+        assert_eq!(Fzf.score(&Line::from("foo"), &['o', 'f']), None);
+        let line = Line::from("foo");
+        assert_eq!(Fzf.score(&line, &[]), Some(Score::new(&line)));
+
+        // 'b' is mid-word, then 'a' and 'r' are consecutive: 16 + (16 + 4) + (16 + 4) = 56
+        let line = Line::from("foobar");
+        let expected = Some(Score {
+            first: 3,
+            last: 6,
+            points: (MAX_SCORE_PER_CHAR * 3 - 56) as usize,
+            line: &line,
+        });
+        assert_eq!(Fzf.score(&line, &['b', 'a', 'r']), expected);
+
+        // the first 'b' is found first, but the one at the word boundary makes a better match
+        let line = Line::from("abxr / bar");
+        let score = Fzf.score(&line, &['b', 'a', 'r']).unwrap();
+        assert_eq!((score.first, score.last), (7, 10));
+
+        assert_eq!(Fzf.positions(&line, &['b', 'a', 'r']), Some(vec![7, 8, 9]));
+
+        // query case doesn't matter
+        assert_eq!(
+            Fzf.score(&line, &['B', 'A', 'R']),
+            Fzf.score(&line, &['b', 'a', 'r'])
+        );
+    }
+
+    #[test]
+    fn test_positions() {
+        assert_eq!(Fzf.positions(&Line::from("foo"), &['q']), None);
+        assert_eq!(Fzf.positions(&Line::from("foo"), &[]), Some(vec![]));
+        assert_eq!(
+            Fzf.positions(&Line::from("foo_bar"), &['f', 'b']),
+            Some(vec![0, 4])
+        );
+        assert_eq!(
+            Fzf.positions(&Line::from("src/fooBar.rs"), &['s', 'f', 'b', 'r', 's']),
+            Some(vec![0, 4, 7, 11, 12])
+        );
+    }
+
+    #[test]
+    fn test_ranking() {
+        // boundaries beat mid-word matches
+        assert_eq!(
+            ranked(&["xfoxbarx", "foo_bar"], "fb"),
+            vec!["foo_bar", "xfoxbarx"]
+        );
+        // camelCase transitions count as boundaries
+        assert_eq!(
+            ranked(&["foobar", "fooBar"], "fb"),
+            vec!["fooBar", "foobar"]
+        );
+        // consecutive characters beat scattered ones
+        assert_eq!(ranked(&["s_r_c", "src"], "src"), vec!["src", "s_r_c"]);
+    }
+}
@@ -0,0 +1,171 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, ErrorKind, Write},
+    path::PathBuf,
+};
+
+// the most queries loaded from the history file
+const MAX_ENTRIES: usize = 1000;
+
+// previously accepted queries, oldest first, and which of them is being recalled
+pub struct History {
+    path: PathBuf,
+    entries: Vec<String>,
+    // the entry being recalled, or `entries.len()` if none is
+    position: usize,
+    // the query typed before recalling started, restored by moving past the newest entry
+    draft: String,
+    // the query accepted this session, if it's to be added to the file
+    accepted: Option<String>,
+}
+
+impl History {
+    pub fn load(path: PathBuf) -> io::Result<Self> {
+        let contents = match fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        // queries are only ever appended to the file, so only the newest are kept
+        let lines: Vec<&str> = contents.lines().collect();
+        let entries: Vec<String> = lines[lines.len().saturating_sub(MAX_ENTRIES)..]
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        Ok(Self {
+            path,
+            position: entries.len(),
+            entries,
+            draft: String::new(),
+            accepted: None,
+        })
+    }
+
+    // recalls the entry before the current one. `query` is the query being typed, which is kept
+    // if this starts recalling.
+    pub fn previous(&mut self, query: &str) -> Option<&str> {
+        if self.position == 0 {
+            return None;
+        }
+        if self.position == self.entries.len() {
+            self.draft = query.to_string();
+        }
+        self.position -= 1;
+        Some(&self.entries[self.position])
+    }
+
+    // recalls the entry after the current one, or the query typed before recalling started
+    pub fn next(&mut self) -> Option<&str> {
+        if self.position >= self.entries.len() {
+            return None;
+        }
+        self.position += 1;
+        Some(self.entries.get(self.position).unwrap_or(&self.draft))
+    }
+
+    pub fn add(&mut self, query: &str) {
+        if !query.is_empty() && self.entries.last().is_none_or(|last| last != query) {
+            self.entries.push(query.to_string());
+            self.accepted = Some(query.to_string());
+            if self.entries.len() > MAX_ENTRIES {
+                self.entries.drain(..self.entries.len() - MAX_ENTRIES);
+            }
+        }
+        self.position = self.entries.len();
+    }
+
+    // appends the accepted query to the file, rather than rewriting it, so that the queries other
+    // sessions have added since this one loaded it are kept
+    pub fn save(&self) -> io::Result<()> {
+        let Some(query) = &self.accepted else {
+            return Ok(());
+        };
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(format!("{}\n", query).as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(entries: &[&str]) -> History {
+        History {
+            path: PathBuf::new(),
+            entries: entries.iter().map(|e| e.to_string()).collect(),
+            position: entries.len(),
+            draft: String::new(),
+            accepted: None,
+        }
+    }
+
+    #[test]
+    fn test_recall() {
+        let mut h = history(&["foo", "bar"]);
+        assert_eq!(h.next(), None);
+        assert_eq!(h.previous("ba"), Some("bar"));
+        assert_eq!(h.previous("bar"), Some("foo"));
+        assert_eq!(h.previous("foo"), None);
+        assert_eq!(h.next(), Some("bar"));
+        // moving past the newest entry restores what was being typed
+        assert_eq!(h.next(), Some("ba"));
+        assert_eq!(h.next(), None);
+        assert_eq!(history(&[]).previous("foo"), None);
+    }
+
+    #[test]
+    fn test_add() {
+        let mut h = history(&["foo"]);
+        h.previous("");
+        h.add("bar");
+        // adding stops recalling
+        assert_eq!(h.previous(""), Some("bar"));
+        // empty queries and repeats aren't added
+        h.add("");
+        h.add("bar");
+        assert_eq!(h.entries, vec!["foo", "bar"]);
+        for i in 0..MAX_ENTRIES {
+            h.add(&i.to_string());
+        }
+        assert_eq!(h.entries.len(), MAX_ENTRIES);
+        assert_eq!(h.entries[0], "0");
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("star-history-test-{}", std::process::id()));
+        let mut h = history(&[]);
+        h.path = path.clone();
+        h.add("foo");
+        h.save().unwrap();
+        // sessions running at the same time each add their own query
+        let mut first = History::load(path.clone()).unwrap();
+        let mut second = History::load(path.clone()).unwrap();
+        first.add("b a r");
+        second.add("baz");
+        first.save().unwrap();
+        second.save().unwrap();
+        let mut loaded = History::load(path.clone()).unwrap();
+        assert_eq!(loaded.entries, vec!["foo", "b a r", "baz"]);
+        assert_eq!(loaded.previous(""), Some("baz"));
+        // a session that accepts nothing new leaves the file alone
+        loaded.add("baz");
+        loaded.save().unwrap();
+        assert_eq!(History::load(path.clone()).unwrap().entries.len(), 3);
+        // only the newest entries are loaded
+        let contents: String = (0..MAX_ENTRIES + 5).map(|i| format!("{}\n", i)).collect();
+        fs::write(&path, contents).unwrap();
+        let loaded = History::load(path.clone()).unwrap();
+        assert_eq!(loaded.entries.len(), MAX_ENTRIES);
+        assert_eq!(loaded.entries[0], "5");
+        fs::remove_file(path).unwrap();
+        // a missing file is just an empty history
+        assert!(History::load(PathBuf::from("/nonexistent/history"))
+            .unwrap()
+            .entries
+            .is_empty());
+    }
+}
@@ -0,0 +1,33 @@
+//! The matching and ranking behind star, a fuzzy finder based on Gary Bernhardt's Selecta.
+//!
+//! ```
+//! use star::{rank, Line};
+//!
+//! let lines: Vec<Line> = ["src/main.rs", "README.md", "src/lib.rs"]
+//!     .iter()
+//!     .enumerate()
+//!     .map(|(i, l)| Line::new(l.to_string(), i))
+//!     .collect();
+//! let ranked = rank(&lines, "srm");
+//! assert_eq!(ranked.len(), 1);
+//! assert_eq!(ranked[0].line.buf, "src/main.rs");
+//! ```
+
+mod fzf;
+mod line;
+mod matcher;
+mod regex;
+mod score;
+
+pub use crate::{
+    fzf::Fzf,
+    line::Line,
+    matcher::{Matcher, Selecta, Substring},
+    regex::Regex,
+    score::{Order, Scheme, Score, Tiebreak, Tiebreaks},
+};
+
+/// Returns the lines matching `query` using Selecta's algorithm, best first.
+pub fn rank<'a>(lines: &'a [Line], query: &str) -> Vec<Score<'a>> {
+    Selecta::default().rank(lines, query)
+}
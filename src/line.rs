@@ -1,9 +1,78 @@
+/// A line of input. `index` is the line's position in the input.
 #[derive(Debug, PartialEq, Eq)]
 pub struct Line {
     pub index: usize,
     pub buf: String,
-    pub low_buf: String,
-    pub low_char_vec: Vec<(usize, char)>,
+    pub(crate) low_buf: String,
+    pub(crate) low_char_vec: Vec<(usize, char)>,
+    styles: Vec<(usize, String)>,
+}
+
+// returns the length of the escape sequence at the start of `s`, and its parameters if it's an
+// SGR sequence. anything unterminated runs to the end of `s`
+fn escape_sequence(s: &str) -> (usize, Option<&str>) {
+    match s.as_bytes().get(1) {
+        // CSI sequences end with a byte from @ to ~, and only those ending with m set a style
+        Some(b'[') => match s.bytes().skip(2).position(|b| (0x40..=0x7e).contains(&b)) {
+            Some(n) => {
+                let end = n + 2;
+                let params = (s.as_bytes()[end] == b'm').then(|| &s[2..end]);
+                (end + 1, params)
+            }
+            None => (s.len(), None),
+        },
+        // OSC sequences (e.g. the hyperlinks printed by ls --hyperlink) end with BEL or ESC \
+        Some(b']') => {
+            let bel = s.find('\x07').map(|n| n + 1);
+            let st = s[1..].find("\x1b\\").map(|n| n + 3);
+            (bel.into_iter().chain(st).min().unwrap_or(s.len()), None)
+        }
+        // other escape sequences are any bytes from space to / (e.g. the ( of ESC ( B, which
+        // selects a character set) followed by one more
+        Some(_) => {
+            let intermediates = s
+                .bytes()
+                .skip(1)
+                .take_while(|b| (0x20..=0x2f).contains(b))
+                .count();
+            let end = 1 + intermediates;
+            (
+                end + s[end..].chars().next().map_or(0, char::len_utf8),
+                None,
+            )
+        }
+        None => (1, None),
+    }
+}
+
+// updates `style`, an SGR sequence setting the current style from a reset, with the parameters of
+// another SGR sequence, dropping everything before a reset
+fn apply_sgr(style: &mut String, params: &str) {
+    let params: Vec<&str> = params.split(';').collect();
+    let mut start = None;
+    let mut i = 0;
+    while i < params.len() {
+        i += match params[i] {
+            "" | "0" => {
+                start = Some(i + 1);
+                1
+            }
+            // extended colors' own parameters, which may well be 0
+            "38" | "48" | "58" => match params.get(i + 1) {
+                Some(&"5") => 3,
+                Some(&"2") => 5,
+                _ => 1,
+            },
+            _ => 1,
+        };
+    }
+    if let Some(start) = start {
+        style.clear();
+        if start >= params.len() {
+            return;
+        }
+    }
+    style.push_str(&format!("\x1b[{}m", params[start.unwrap_or(0)..].join(";")));
 }
 
 impl Line {
@@ -14,13 +83,57 @@ impl Line {
             low_char_vec: low_buf.char_indices().collect(),
             low_buf,
             buf,
+            styles: Vec::new(),
+        }
+    }
+
+    /// Creates a line from input that may be colored with ANSI escape sequences (e.g. the output
+    /// of `ls --color=always`). The sequences are removed from the text that's matched, and the
+    /// styles they set are kept in `styles`.
+    pub fn with_ansi(input: &str, index: usize) -> Self {
+        let mut buf = String::with_capacity(input.len());
+        let mut styles: Vec<(usize, String)> = Vec::new();
+        let mut style = String::new();
+        let mut chars = 0;
+        let mut rest = input;
+        while let Some(n) = rest.find('\x1b') {
+            buf.push_str(&rest[..n]);
+            chars += rest[..n].chars().count();
+            let (len, params) = escape_sequence(&rest[n..]);
+            rest = &rest[n + len..];
+            let Some(params) = params else {
+                continue;
+            };
+            apply_sgr(&mut style, params);
+            match styles.last_mut() {
+                Some((at, last)) if *at == chars => last.clone_from(&style),
+                Some((_, last)) if *last == style => {}
+                None if style.is_empty() => {}
+                _ => styles.push((chars, style.clone())),
+            }
+        }
+        buf.push_str(rest);
+        Self {
+            styles,
+            ..Self::new(buf, index)
         }
     }
 
+    /// The styles set by ANSI escape sequences in the input (see [`Line::with_ansi`]), as the index
+    /// of the character each starts at and the SGR sequence that sets it from a reset (empty for
+    /// the default style).
+    pub fn styles(&self) -> &[(usize, String)] {
+        &self.styles
+    }
+
     pub fn len(&self) -> usize {
         self.buf.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
     #[cfg(test)]
     pub fn from(s: &str) -> Line {
         Line::new(String::from(s), s.len())
@@ -41,6 +154,42 @@ mod tests {
     fn test_line_length() {
         let l = Line::from("foo");
         assert_eq!(l.len(), 3);
+        assert!(!l.is_empty());
+        assert!(Line::from("").is_empty());
+    }
+
+    #[test]
+    fn test_with_ansi() {
+        let l = Line::with_ansi("\x1b[01;34msrc\x1b[0m/\x1b[31mMAIN\x1b[1m.rs\x1b[m", 3);
+        assert_eq!(l.buf, "src/MAIN.rs");
+        assert_eq!(l.low_buf, "src/main.rs");
+        assert_eq!(l.index, 3);
+        assert_eq!(
+            l.styles,
+            vec![
+                (0, String::from("\x1b[01;34m")),
+                (3, String::new()),
+                (4, String::from("\x1b[31m")),
+                (8, String::from("\x1b[31m\x1b[1m")),
+                (11, String::new()),
+            ]
+        );
+        // color 0 isn't a reset, sequences at the same place are merged and other escape
+        // sequences are dropped
+        let l = Line::with_ansi("\x1b[38;5;0;1ma\x1b[K\x1b[0;32m\x1b[4mb\x1b[0m", 0);
+        assert_eq!(l.buf, "ab");
+        assert_eq!(
+            l.styles,
+            vec![
+                (0, String::from("\x1b[38;5;0;1m")),
+                (1, String::from("\x1b[32m\x1b[4m")),
+                (2, String::new()),
+            ]
+        );
+        let l = Line::with_ansi("\x1b]8;;file:///tmp\x1b\\tmp\x1b]8;;\x07/ \x1b(B\x1b[", 0);
+        assert_eq!(l.buf, "tmp/ ");
+        assert!(l.styles.is_empty());
+        assert_eq!(Line::with_ansi("plain", 5), Line::from("plain"));
     }
 
     #[test]
@@ -1,15 +1,28 @@
+mod cache;
 mod color;
 mod console;
 mod event_loop;
-mod line;
+mod frecency;
+mod history;
+mod matches;
 mod render;
-mod score;
+mod screen;
+mod selection;
+mod theme;
+mod view;
+mod worker;
 
-use crate::line::Line;
 use clap::{crate_authors, crate_description, crate_name, crate_version, App, Arg};
-use color::{get_colors, Colors};
+use color::get_colors;
+use event_loop::Config;
+use frecency::{Frecency, FrecentMatcher};
+use history::History;
+use render::{Border, Counter, Spacing};
+use selection::TaggedOrder;
+use star::{Fzf, Line, Matcher, Regex, Scheme, Selecta, Substring, Tiebreaks};
 use std::{
     io::{self, BufRead, Error, ErrorKind},
+    path::PathBuf,
     process::exit,
 };
 
@@ -22,16 +35,70 @@ fn error_exit(err: Error) {
     }
 }
 
-fn run(initial_search: &str, height: usize, colors: (Colors, Colors), multiple: bool) {
-    let stdin_lines: Vec<Line> = io::stdin()
-        .lock()
-        .lines()
-        .map_while(Result::ok)
+// the first `header_lines` lines of input are added to the config's header rather than matched.
+// with `ansi`, escape sequences are taken out of the input, keeping the styles of lines to match
+fn run(
+    config: Config,
+    header_lines: usize,
+    ansi: bool,
+    mut frecency: Option<Frecency>,
+    mut history: Option<History>,
+) {
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines().map_while(Result::ok);
+    let mut header = config.header.to_vec();
+    header.extend(lines.by_ref().take(header_lines).map(|l| {
+        if ansi {
+            Line::with_ansi(&l, 0).buf
+        } else {
+            l
+        }
+    }));
+    let stdin_lines: Vec<Line> = lines
         .enumerate()
-        .map(|(l, i)| Line::new(i, l))
+        .map(|(l, i)| {
+            if ansi {
+                Line::with_ansi(&i, l)
+            } else {
+                Line::new(i, l)
+            }
+        })
         .collect();
-    match event_loop::run(stdin_lines, initial_search, height, colors, multiple) {
-        Ok(l) => println!("{}", l),
+    let config = Config {
+        header: &header,
+        ..config
+    };
+    let now = frecency::now();
+    let frecent;
+    let config = match &frecency {
+        Some(f) => {
+            frecent = (
+                FrecentMatcher::new(config.matcher, &stdin_lines, f, now),
+                FrecentMatcher::new(config.regex_matcher, &stdin_lines, f, now),
+            );
+            Config {
+                matcher: &frecent.0,
+                regex_matcher: &frecent.1,
+                ..config
+            }
+        }
+        None => config,
+    };
+    match event_loop::run(stdin_lines, &config, history.as_mut()) {
+        Ok(l) => {
+            println!("{}", l);
+            if let Some(Err(e)) = history.map(|h| h.save()) {
+                eprintln!("couldn't save query history: {}", e);
+            }
+            if let Some(f) = frecency.as_mut() {
+                for line in l.lines() {
+                    f.record(line, now);
+                }
+                if let Err(e) = f.save() {
+                    eprintln!("couldn't save selection history: {}", e);
+                }
+            }
+        }
         Err(e) => error_exit(e),
     };
 }
@@ -49,6 +116,118 @@ fn main() {
                 .takes_value(true)
                 .default_value("21"),
         )
+        .arg(
+            Arg::with_name("algorithm")
+                .short("a")
+                .long("algorithm")
+                .help("Specify the matching algorithm")
+                .long_help(
+                    "Specify the matching algorithm: \"selecta\" (shortest match wins), \"fzf\" \
+                     (slower, optimal alignment with bonuses for word boundaries, camelCase and \
+                     consecutive characters) or \"substring\" (fastest, exact substrings only)",
+                )
+                .takes_value(true)
+                .possible_values(&["selecta", "fzf", "substring"])
+                .default_value("selecta"),
+        )
+        .arg(
+            Arg::with_name("scheme")
+                .long("scheme")
+                .help("Specify how lines are interpreted when scoring")
+                .long_help(
+                    "Specify how lines are interpreted when scoring: \"default\" (plain text) or \
+                     \"path\" (file paths: camelCase transitions count as word boundaries, matches \
+                     in the basename rank above matches in directories, and deep paths rank \
+                     lower). Only applies to the selecta algorithm",
+                )
+                .takes_value(true)
+                .possible_values(&["default", "path"])
+                .default_value("default"),
+        )
+        .arg(
+            Arg::with_name("typos")
+                .long("typos")
+                .value_name("N")
+                .help("Tolerate up to N typos in the query")
+                .long_help(
+                    "Tolerate up to N typos in the query (at most one for every four characters): \
+                     extra, wrong or swapped characters. Matches with typos rank below every \
+                     match without them. Only applies to the selecta algorithm",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("tiebreak")
+                .long("tiebreak")
+                .help("Specify how to order equally good matches")
+                .long_help(
+                    "Specify how to order equally good matches, as a comma-separated list of \
+                     criteria applied in turn: \"length\" (shorter lines first), \"begin\" \
+                     (matches starting earlier first), \"end\" (matches ending closer to the end \
+                     of the line first) or \"index\" (input order). Any remaining ties are \
+                     broken by input order",
+                )
+                .takes_value(true)
+                .default_value("length"),
+        )
+        .arg(
+            Arg::with_name("regex")
+                .long("regex")
+                .help("Match the query as a regular expression (toggle with Ctrl-R)"),
+        )
+        .arg(
+            Arg::with_name("no-sort")
+                .long("no-sort")
+                .help("Show matches in input order rather than by score (toggle with Ctrl-S)"),
+        )
+        .arg(
+            Arg::with_name("ansi")
+                .long("ansi")
+                .help("Show the colors of input colored with ANSI escape sequences")
+                .long_help(
+                    "Show the colors of input colored with ANSI escape sequences (e.g. by ls \
+                     --color=always), which are left out of matching and of the selected line",
+                ),
+        )
+        .arg(
+            Arg::with_name("mouse")
+                .long("mouse")
+                .help("Enable the mouse")
+                .long_help(
+                    "Enable the mouse: click a line to select it, double-click it to accept it and \
+                     scroll with the wheel",
+                ),
+        )
+        .arg(
+            Arg::with_name("history-key")
+                .long("history-key")
+                .value_name("NAME")
+                .help("Rank lines selected often and recently higher, remembering them under NAME")
+                .long_help(
+                    "Remember selections under NAME (e.g. \"files\") and rank lines that were \
+                     selected often and recently above equally good matches. Selections are kept \
+                     in $XDG_DATA_HOME/star/frecency/NAME",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("history")
+                .long("history")
+                .value_name("FILE")
+                .help("Save accepted queries to FILE; Ctrl-P and Ctrl-N recall them")
+                .long_help(
+                    "Save accepted queries to FILE. Ctrl-P and Ctrl-N then recall previous and \
+                     next queries instead of moving the selection, which the arrow keys still do",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("cache-size")
+                .long("cache-size")
+                .help("Specify how much memory (in megabytes) to use for caching search results")
+                .takes_value(true)
+                .default_value("512"),
+        )
         .arg(
             Arg::with_name("search")
                 .short("s")
@@ -58,6 +237,96 @@ fn main() {
                 .default_value("")
                 .hide_default_value(true),
         )
+        .arg(
+            Arg::with_name("prompt")
+                .long("prompt")
+                .help("Specify the prompt shown before the query")
+                .takes_value(true)
+                .default_value("> "),
+        )
+        .arg(
+            Arg::with_name("pointer")
+                .long("pointer")
+                .help("Specify a marker shown before the selected line")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("marker")
+                .long("marker")
+                .help("Specify the marker shown before tagged lines in multiple selection mode")
+                .takes_value(true)
+                .default_value("+"),
+        )
+        .arg(
+            Arg::with_name("counter")
+                .long("counter")
+                .help("Specify where the number of matches is shown")
+                .long_help(
+                    "Specify where the number of matches (out of the number of lines, with the \
+                     number of tagged lines and whether scoring is still in progress) is shown: \
+                     \"left\" (before the prompt), \"right\" (at the right edge of the search \
+                     line), \"separate\" (on a line of its own) or \"hidden\"",
+                )
+                .takes_value(true)
+                .possible_values(&["left", "right", "separate", "hidden"]),
+        )
+        .arg(
+            Arg::with_name("border")
+                .long("border")
+                .help("Draw a border around the prompt and matches")
+                .takes_value(true)
+                .possible_values(&["rounded", "sharp", "none"]),
+        )
+        .arg(
+            Arg::with_name("margin")
+                .long("margin")
+                .help("Leave space around the border")
+                .long_help(
+                    "Leave space around the border (or where it would be): either a number of \
+                     rows above and below and columns either side, or the rows and then the \
+                     columns, separated by a comma (e.g. \"1,2\")",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("padding")
+                .long("padding")
+                .help("Leave space inside the border")
+                .long_help(
+                    "Leave space inside the border (or where it would be), given in the same way \
+                     as --margin",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("header")
+                .long("header")
+                .value_name("TEXT")
+                .help("Show TEXT above the matches")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("header-lines")
+                .long("header-lines")
+                .value_name("N")
+                .help("Show the first N lines of input above the matches, instead of matching them")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("theme")
+                .long("theme")
+                .value_name("NAME")
+                .help("Use a color theme: dark, light, solarized or one from the config file")
+                .long_help(
+                    "Use a color theme: one of the built-in themes (default, dark, light and \
+                     solarized) or one defined in $XDG_CONFIG_HOME/star/config. The --color-* \
+                     options override the colors it sets. Colors are given as a name (e.g. \
+                     red), a number from the 256-color palette or #RRGGBB, optionally followed \
+                     by attributes (bold, dim, italic, underline and reverse) separated by colons \
+                     (e.g. red:bold:underline). Attributes can also be given without a color",
+                )
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("color-normal-fg")
                 .long("color-normal-fg")
@@ -118,12 +387,51 @@ fn main() {
                 .help("Background color of tagged indicator (+) in multiple-selection mode")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("color-header-fg")
+                .long("color-header-fg")
+                .help("Foreground color of header lines")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("color-header-bg")
+                .long("color-header-bg")
+                .help("Background color of header lines")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("multiple")
                 .short("m")
                 .long("multiple")
                 .takes_value(false)
                 .help("Enable multiple selection mode"),
+        )
+        .arg(
+            Arg::with_name("limit")
+                .long("limit")
+                .value_name("N")
+                .takes_value(true)
+                .requires("multiple")
+                .help("Tag at most N lines in multiple selection mode"),
+        )
+        .arg(
+            Arg::with_name("tagged-order")
+                .long("tagged-order")
+                .help("Specify the order tagged lines are printed in")
+                .long_help(
+                    "Specify the order tagged lines are printed in: \"tag\" (the order they were \
+                     tagged in), \"input\" (the order they were read in) or \"score\" (the order \
+                     they're listed in for the final query, followed by any it doesn't match)",
+                )
+                .takes_value(true)
+                .possible_values(&["tag", "input", "score"])
+                .requires("multiple"),
+        )
+        .arg(
+            Arg::with_name("accept-tagged")
+                .long("accept-tagged")
+                .requires("multiple")
+                .help("Print the tagged lines on Enter, as on Alt-Enter"),
         );
     if termion::is_tty(&io::stdin()) {
         app.print_long_help().unwrap();
@@ -142,11 +450,165 @@ fn main() {
         },
         None => 21,
     };
+    let cache_size = match matches.value_of("cache-size") {
+        Some(c) => match c.parse::<usize>() {
+            Ok(c) => c.saturating_mul(1024 * 1024),
+            Err(_) => {
+                return error_exit(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("invalid cache size specification: \"{}\"", c),
+                ));
+            }
+        },
+        None => 512 * 1024 * 1024,
+    };
+    let scheme = match matches.value_of("scheme") {
+        Some("path") => Scheme::Path,
+        _ => Scheme::Default,
+    };
+    let typos = match matches.value_of("typos") {
+        Some(t) => match t.parse::<usize>() {
+            Ok(t) => t,
+            Err(_) => {
+                return error_exit(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("invalid typos specification: \"{}\"", t),
+                ));
+            }
+        },
+        None => 0,
+    };
+    for (option, used) in [
+        ("--scheme", scheme != Scheme::Default),
+        ("--typos", typos > 0),
+    ] {
+        if used && matches.value_of("algorithm") != Some("selecta") {
+            return error_exit(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} only applies to the selecta algorithm", option),
+            ));
+        }
+    }
+    let matcher: Box<dyn Matcher> = match matches.value_of("algorithm") {
+        Some("fzf") => Box::new(Fzf),
+        Some("substring") => Box::new(Substring),
+        _ => Box::new(Selecta { scheme, typos }),
+    };
+    let tiebreaks = match matches.value_of("tiebreak") {
+        Some(t) => match t.parse::<Tiebreaks>() {
+            Ok(t) => t,
+            Err(e) => {
+                return error_exit(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("invalid tiebreak specification: {}", e),
+                ));
+            }
+        },
+        None => Tiebreaks::default(),
+    };
     let multiple = matches.occurrences_of("multiple") > 0;
+    let limit = match matches.value_of("limit") {
+        Some(l) => match l.parse::<usize>() {
+            Ok(l) if l > 0 => Some(l),
+            _ => {
+                return error_exit(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("invalid limit specification: \"{}\"", l),
+                ));
+            }
+        },
+        None => None,
+    };
+    let tagged_order = match matches.value_of("tagged-order") {
+        Some("input") => TaggedOrder::Input,
+        Some("score") => TaggedOrder::Score,
+        _ => TaggedOrder::Tag,
+    };
+    let header_lines = match matches.value_of("header-lines") {
+        Some(h) => match h.parse::<usize>() {
+            Ok(h) => h,
+            Err(_) => {
+                return error_exit(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("invalid header lines specification: \"{}\"", h),
+                ));
+            }
+        },
+        None => 0,
+    };
+    let header: Vec<String> = matches
+        .value_of("header")
+        .map_or(vec![], |h| h.lines().map(String::from).collect());
+    let spacing = |option: &str| match matches.value_of(option) {
+        Some(s) => s.parse::<Spacing>().map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid {} specification: {}", option, e),
+            )
+        }),
+        None => Ok(Spacing::default()),
+    };
+    let (margin, padding) = match (spacing("margin"), spacing("padding")) {
+        (Ok(m), Ok(p)) => (m, p),
+        (Err(e), _) | (_, Err(e)) => return error_exit(e),
+    };
     let search = matches.value_of("search").unwrap_or("");
     let colors = match get_colors(&matches) {
         Ok(c) => c,
         Err(e) => return error_exit(e),
     };
-    run(search, height, colors, multiple);
+    let frecency = match matches.value_of("history-key") {
+        Some(key) => match Frecency::open(key) {
+            Ok(f) => Some(f),
+            Err(e) => return error_exit(e),
+        },
+        None => None,
+    };
+    let config = Config {
+        initial_search: search,
+        height,
+        colors,
+        multiple,
+        limit,
+        tagged_order,
+        accept_tagged: matches.occurrences_of("accept-tagged") > 0,
+        cache_size,
+        matcher: matcher.as_ref(),
+        regex_matcher: &Regex,
+        regex: matches.occurrences_of("regex") > 0,
+        tiebreaks,
+        sort: matches.occurrences_of("no-sort") == 0,
+        mouse: matches.occurrences_of("mouse") > 0,
+        header: &header,
+        prompt: matches.value_of("prompt").unwrap_or("> "),
+        pointer: matches.value_of("pointer").unwrap_or(""),
+        marker: matches.value_of("marker").unwrap_or("+"),
+        counter: match matches.value_of("counter") {
+            Some("right") => Counter::Right,
+            Some("separate") => Counter::Separate,
+            Some("hidden") => Counter::Hidden,
+            _ => Counter::Left,
+        },
+        border: match matches.value_of("border") {
+            Some("rounded") => Border::Rounded,
+            Some("sharp") => Border::Sharp,
+            _ => Border::None,
+        },
+        margin,
+        padding,
+    };
+    let history = match matches.value_of("history") {
+        Some(path) => match History::load(PathBuf::from(path)) {
+            Ok(h) => Some(h),
+            Err(e) => {
+                return error_exit(Error::new(
+                    e.kind(),
+                    format!("couldn't read query history \"{}\": {}", path, e),
+                ));
+            }
+        },
+        None => None,
+    };
+    let ansi = matches.occurrences_of("ansi") > 0;
+    run(config, header_lines, ansi, frecency, history);
 }
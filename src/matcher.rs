@@ -0,0 +1,414 @@
+use crate::{
+    line::Line,
+    score::{calculate_score_with, match_positions_with, Scheme, Score},
+};
+use rayon::prelude::*;
+use std::{cell::RefCell, cmp::min};
+
+// matches with typos are penalized this much per typo: more than any line could be given for a
+// match without them, so that those always rank first. a match costs at most a point for every
+// character of the line and of the query and a couple more, and every character of a line takes
+// up at least 10 bytes, so no line fits in memory that could cost an eighth of the address space.
+const TYPO_PENALTY: usize = usize::MAX / 8 + 1;
+// queries are allowed one typo for every this many characters, since short queries with typos
+// fixed match almost anything
+const CHARS_PER_TYPO: usize = 4;
+
+thread_local! {
+    // lines are scored one at a time, so each thread keeps the typo fixes for the last query it
+    // tried them for rather than working them out again for every line that doesn't match
+    static TYPO_FIXES: RefCell<Option<TypoFixes>> = const { RefCell::new(None) };
+}
+
+/// A scoring algorithm.
+pub trait Matcher: Sync {
+    /// Returns the score for `line`, or `None` if it doesn't match `query`.
+    fn score<'a>(&self, line: &'a Line, query: &[char]) -> Option<Score<'a>>;
+
+    /// Returns the positions of the characters in `line` matched by `query`, or `None` if it
+    /// doesn't match. By default, every position in the matched range.
+    fn positions(&self, line: &Line, query: &[char]) -> Option<Vec<usize>> {
+        self.score(line, query).map(|s| (s.first..s.last).collect())
+    }
+
+    /// Returns why `query` can't be matched against, if it can't. By default every query can.
+    fn validate(&self, _query: &[char]) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Whether every line matching `query` is guaranteed to also match `other`, in which case the
+    /// lines matching `other` are the only ones that need to be scored against `query`. For
+    /// subsequence matchers this is true when `other` is a subsequence of `query`.
+    fn narrows(&self, query: &str, other: &str) -> bool {
+        is_subsequence(other, query)
+    }
+
+    /// Scores every line, returning only the matches, best first.
+    fn rank<'a>(&self, lines: &'a [Line], query: &str) -> Vec<Score<'a>> {
+        let query: Vec<char> = query.chars().collect();
+        let mut scores: Vec<Score> = lines
+            .par_iter()
+            .filter_map(|l| self.score(l, &query))
+            .collect();
+        scores.par_sort_unstable_by(Score::cmp);
+        scores
+    }
+}
+
+// whether every character of `sub` appears in `s`, in order, ignoring case
+fn is_subsequence(sub: &str, s: &str) -> bool {
+    let mut s = s.chars().flat_map(char::to_lowercase);
+    sub.chars()
+        .flat_map(char::to_lowercase)
+        .all(|c| s.any(|q| q == c))
+}
+
+/// Selecta's algorithm: the query's characters must appear in order, and the shortest match wins,
+/// with runs of sequential characters and characters at word boundaries counting as one. The best
+/// alignment of the query is found, rather than the first.
+///
+/// Up to `typos` typos (one for every four characters of the query) can be tolerated: a line that
+/// doesn't match the query still matches, ranked below every line that does, if it matches the
+/// query with that many characters dropped or pairs of adjacent characters swapped.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Selecta {
+    pub scheme: Scheme,
+    pub typos: usize,
+}
+
+impl Selecta {
+    pub fn new(scheme: Scheme) -> Self {
+        Self { scheme, typos: 0 }
+    }
+
+    fn allowed_typos(&self, query_len: usize) -> usize {
+        min(self.typos, query_len / CHARS_PER_TYPO)
+    }
+
+    // the best match for the query with the fewest typos fixed, along with the fixed query and the
+    // number of typos, if the query itself doesn't match
+    fn typo_match<'a>(
+        &self,
+        line: &'a Line,
+        query: &[char],
+    ) -> Option<(Score<'a>, Vec<char>, usize)> {
+        let allowed = self.allowed_typos(query.len());
+        TYPO_FIXES.with_borrow_mut(|fixes| {
+            if fixes
+                .as_ref()
+                .is_none_or(|f| f.query != query || f.fixes.len() != allowed)
+            {
+                *fixes = Some(TypoFixes::new(query, allowed));
+            }
+            fixes.as_ref().unwrap().best_match(line, self.scheme)
+        })
+    }
+}
+
+// the queries a line that doesn't match `query` is tried against instead
+struct TypoFixes {
+    query: Vec<char>,
+    // the queries with one typo fixed, then with two, and so on
+    fixes: Vec<Vec<Vec<char>>>,
+    // how many times each of the query's characters appears in it, lowercased. characters that
+    // lowercase to more than one are left out.
+    counts: Vec<(char, usize)>,
+}
+
+impl TypoFixes {
+    fn new(query: &[char], allowed: usize) -> Self {
+        let mut fixes: Vec<Vec<Vec<char>>> = Vec::with_capacity(allowed);
+        let mut queries = vec![query.to_vec()];
+        for _ in 0..allowed {
+            queries = queries.iter().flat_map(|q| typo_fixes(q)).collect();
+            queries.sort_unstable();
+            queries.dedup();
+            fixes.push(queries.clone());
+        }
+        let mut counts: Vec<(char, usize)> = Vec::new();
+        for c in query {
+            let mut lower = c.to_lowercase();
+            let (Some(c), None) = (lower.next(), lower.next()) else {
+                continue;
+            };
+            match counts.iter_mut().find(|(d, _)| *d == c) {
+                Some((_, n)) => *n += 1,
+                None => counts.push((c, 1)),
+            }
+        }
+        Self {
+            query: query.to_vec(),
+            fixes,
+            counts,
+        }
+    }
+
+    // fixing typos only ever drops characters or swaps them around, so a line short of more of
+    // the query's characters than there are typos allowed can't match any fix, and one short of
+    // some can only match fixes with at least that many typos
+    fn missing(&self, line: &Line) -> usize {
+        self.counts
+            .iter()
+            .map(|&(c, n)| {
+                let found = line.low_char_vec.iter().filter(|&&(_, l)| l == c).count();
+                n.saturating_sub(found)
+            })
+            .sum()
+    }
+
+    fn best_match<'a>(
+        &self,
+        line: &'a Line,
+        scheme: Scheme,
+    ) -> Option<(Score<'a>, Vec<char>, usize)> {
+        let fewest = self.missing(line).max(1);
+        for (i, queries) in self.fixes.iter().enumerate().skip(fewest - 1) {
+            let best = queries
+                .iter()
+                .filter_map(|q| calculate_score_with(line, q, scheme).map(|s| (s, q)))
+                .min_by(|(a, _), (b, _)| Score::cmp(a, b));
+            if let Some((score, fixed)) = best {
+                return Some((score, fixed.clone(), i + 1));
+            }
+        }
+        None
+    }
+}
+
+// the queries one typo away from `query`: with one character dropped, which fixes an extra
+// character (or a wrong one, since anything can come between matched characters), or with two
+// adjacent characters swapped
+fn typo_fixes(query: &[char]) -> Vec<Vec<char>> {
+    let dropped = (0..query.len()).map(|i| [&query[..i], &query[i + 1..]].concat());
+    let swapped = (1..query.len())
+        .filter(|&i| query[i - 1] != query[i])
+        .map(|i| {
+            let mut q = query.to_vec();
+            q.swap(i - 1, i);
+            q
+        });
+    dropped.chain(swapped).collect()
+}
+
+impl Matcher for Selecta {
+    fn score<'a>(&self, line: &'a Line, query: &[char]) -> Option<Score<'a>> {
+        let score = calculate_score_with(line, query, self.scheme);
+        if score.is_some() || self.allowed_typos(query.len()) == 0 {
+            return score;
+        }
+        self.typo_match(line, query).map(|(score, _, typos)| Score {
+            // matches with 8 or more typos all rank last together
+            points: typos
+                .saturating_mul(TYPO_PENALTY)
+                .saturating_add(score.points),
+            ..score
+        })
+    }
+
+    fn positions(&self, line: &Line, query: &[char]) -> Option<Vec<usize>> {
+        match_positions_with(line, query, self.scheme).or_else(|| {
+            let (_, fixed, _) = self.typo_match(line, query)?;
+            match_positions_with(line, &fixed, self.scheme)
+        })
+    }
+
+    // a swap is never fixed by fewer drops than one, and dropping characters from a query drops
+    // no more from any subsequence of it, so the lines matching a query are among those matching
+    // a subsequence of it, as long as that's allowed as many typos
+    fn narrows(&self, query: &str, other: &str) -> bool {
+        let allowed_typos = |q: &str| self.allowed_typos(q.chars().count());
+        is_subsequence(other, query) && allowed_typos(other) >= allowed_typos(query)
+    }
+}
+
+/// Matches lines containing the query verbatim (ignoring case). Fastest, but least forgiving.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Substring;
+
+impl Matcher for Substring {
+    fn score<'a>(&self, line: &'a Line, query: &[char]) -> Option<Score<'a>> {
+        if query.is_empty() {
+            return Some(Score::new(line));
+        }
+        let query: Vec<char> = query.iter().flat_map(|c| c.to_lowercase()).collect();
+        let chars: Vec<char> = line.low_char_vec.iter().map(|t| t.1).collect();
+        chars
+            .windows(query.len())
+            .position(|w| w == query.as_slice())
+            .map(|first| Score {
+                first,
+                last: first + query.len(),
+                points: 1,
+                line,
+            })
+    }
+
+    fn narrows(&self, query: &str, other: &str) -> bool {
+        query.to_lowercase().contains(&other.to_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::score::calculate_score;
+
+    #[test]
+    fn test_is_subsequence() {
+        assert!(is_subsequence("", "foo"));
+        assert!(is_subsequence("fo", "foo"));
+        assert!(is_subsequence("fb", "foobar"));
+        assert!(is_subsequence("FB", "foobar"));
+        assert!(is_subsequence("fb", "FOOBAR"));
+        assert!(!is_subsequence("bf", "foobar"));
+        assert!(!is_subsequence("foo", "fo"));
+    }
+
+    #[test]
+    fn test_selecta() {
+        let line = Line::from("foobar");
+        assert_eq!(
+            Selecta::default().score(&line, &['b', 'a', 'r']),
+            calculate_score(&line, &['b', 'a', 'r'])
+        );
+        assert_eq!(Selecta::default().score(&Line::from("foo"), &['b']), None);
+        assert_eq!(
+            Selecta::default().positions(&Line::from("f_x_b_bar"), &['b', 'a', 'r']),
+            Some(vec![6, 7, 8])
+        );
+        assert!(Selecta::default().narrows("fbr", "fr"));
+        assert!(!Selecta::default().narrows("fbr", "rf"));
+    }
+
+    #[test]
+    fn test_typo_fixes() {
+        let fixes = typo_fixes(&['a', 'b', 'b']);
+        let expected = [
+            vec!['b', 'b'],
+            vec!['a', 'b'],
+            vec!['a', 'b'],
+            vec!['b', 'a', 'b'],
+        ];
+        assert_eq!(fixes, expected);
+        assert!(typo_fixes(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_typo_fixes_missing() {
+        let query: Vec<char> = "MiaNn".chars().collect();
+        let fixes = TypoFixes::new(&query, 2);
+        assert_eq!(fixes.fixes.len(), 2);
+        assert_eq!(fixes.counts, vec![('m', 1), ('i', 1), ('a', 1), ('n', 2)]);
+        assert_eq!(fixes.missing(&Line::from("src/main.rs")), 1);
+        assert_eq!(fixes.missing(&Line::from("nanami")), 0);
+        assert_eq!(fixes.missing(&Line::from("foo")), 5);
+        let best = |line: &str| {
+            let line = Line::from(line);
+            fixes
+                .best_match(&line, Scheme::Default)
+                .map(|(_, fixed, typos)| (fixed.into_iter().collect::<String>(), typos))
+        };
+        // lines short of more characters than there are typos allowed aren't even aligned
+        assert_eq!(best("xyz.rs"), None);
+        assert_eq!(best("mian"), Some(("MiaN".to_string(), 1)));
+        assert_eq!(best("main"), Some(("MaiN".to_string(), 2)));
+    }
+
+    #[test]
+    fn test_selecta_typos() {
+        let exact = Selecta::default();
+        let tolerant = Selecta { typos: 2, ..exact };
+        let query: Vec<char> = "mian.rs".chars().collect();
+        let line = Line::from("src/main.rs");
+        assert_eq!(exact.score(&line, &query), None);
+        let score = tolerant.score(&line, &query).unwrap();
+        assert_eq!(score.points, TYPO_PENALTY + 2);
+        // the swapped characters are found in their right order
+        assert_eq!(
+            tolerant.positions(&line, &query),
+            Some(vec![4, 5, 6, 7, 8, 9, 10])
+        );
+        // an exact match ranks above any match with typos
+        let lines = [Line::from("src/main.rs"), Line::from("m_i_a_n/x.rs")];
+        let ranked: Vec<&str> = tolerant
+            .rank(&lines, "mian.rs")
+            .iter()
+            .map(|s| s.line.buf.as_str())
+            .collect();
+        assert_eq!(ranked, vec!["m_i_a_n/x.rs", "src/main.rs"]);
+        // however far apart the exactly matched characters are
+        let lines = [
+            Line::from("src/main.rs"),
+            Line::from(&format!("mia{}n.rs", " ".repeat(70_000))),
+        ];
+        let ranked: Vec<usize> = tolerant
+            .rank(&lines, "mian.rs")
+            .iter()
+            .map(|s| s.line.len())
+            .collect();
+        assert_eq!(ranked, vec![70_007, 11]);
+        // short queries get fewer typos
+        let line = Line::from("foo");
+        assert_eq!(tolerant.score(&line, &['f', 'x', 'o']), None);
+        assert!(tolerant.score(&line, &['f', 'x', 'o', 'o']).is_some());
+        assert_eq!(tolerant.score(&line, &['f', 'x', 'o', 'o', 'y']), None);
+        let query: Vec<char> = "axbcdefyg".chars().collect();
+        assert!(tolerant.score(&Line::from("abcdefgh"), &query).is_some());
+        // candidates can only be reused from queries allowed as many typos
+        assert!(!tolerant.narrows("mian", "mia"));
+        assert!(tolerant.narrows("mian.rs", "mian"));
+        assert!(exact.narrows("mian", "mia"));
+    }
+
+    #[test]
+    fn test_substring() {
+        let line = Line::from("fooBARbaz");
+        let expected = Some(Score {
+            first: 3,
+            last: 6,
+            points: 1,
+            line: &line,
+        });
+        assert_eq!(Substring.score(&line, &['b', 'a', 'r']), expected);
+        assert_eq!(Substring.score(&line, &['b', 'r']), None);
+        assert_eq!(
+            Substring.positions(&line, &['b', 'a', 'r']),
+            Some(vec![3, 4, 5])
+        );
+        assert_eq!(Substring.score(&line, &[]), Some(Score::new(&line)));
+        assert!(Substring.narrows("foobar", "OBA"));
+        assert!(!Substring.narrows("foobar", "fb"));
+    }
+
+    #[test]
+    fn test_rank() {
+        let lines = [
+            Line::from("b/a/r"),
+            Line::from("foo"),
+            Line::from("foobar"),
+            Line::from("bar"),
+        ];
+        let ranked = |matcher: &dyn Matcher, query: &str| -> Vec<String> {
+            matcher
+                .rank(&lines, query)
+                .iter()
+                .map(|s| s.line.buf.clone())
+                .collect()
+        };
+        assert_eq!(
+            ranked(&Selecta::default(), "bar"),
+            vec!["bar", "foobar", "b/a/r"]
+        );
+        assert_eq!(ranked(&Substring, "bar"), vec!["bar", "foobar"]);
+        // an empty query matches everything
+        assert_eq!(ranked(&Selecta::default(), "").len(), 4);
+        // the path scheme prefers matches in the basename
+        let lines = [Line::from("main/lib.rs"), Line::from("lib/main.rs")];
+        let ranked: Vec<&str> = Selecta::new(Scheme::Path)
+            .rank(&lines, "main")
+            .iter()
+            .map(|s| s.line.buf.as_str())
+            .collect();
+        assert_eq!(ranked, vec!["lib/main.rs", "main/lib.rs"]);
+    }
+}
@@ -0,0 +1,67 @@
+use star::{Order, Score};
+use std::{
+    cmp::{max, min},
+    ops::Deref,
+};
+
+// the scores matching a query. only the first `sorted` scores are guaranteed to be in order; the
+// rest are all ordered after them, but not necessarily relative to each other. since only a
+// window of matches is ever displayed, fully sorting every match would mostly be wasted work.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Matches<'a> {
+    scores: Vec<Score<'a>>,
+    sorted: usize,
+    order: Order,
+}
+
+impl<'a> Matches<'a> {
+    pub fn new(scores: Vec<Score<'a>>, sorted: usize, order: Order) -> Self {
+        let mut matches = Self {
+            scores,
+            sorted: 0,
+            order,
+        };
+        matches.sort_to(sorted);
+        matches
+    }
+
+    pub fn sorted(&self) -> usize {
+        self.sorted
+    }
+
+    pub fn order(&self) -> Order {
+        self.order
+    }
+
+    // make sure the first `n` scores are in order. every extension has to partition all of the
+    // unsorted scores, so the sorted window at least doubles each time to keep scrolling cheap.
+    fn sort_to(&mut self, n: usize) {
+        if n <= self.sorted {
+            return;
+        }
+        let n = min(max(n, self.sorted * 2), self.scores.len());
+        let order = self.order;
+        let cmp = |a: &Score, b: &Score| order.cmp(a, b);
+        let unsorted = &mut self.scores[self.sorted..];
+        let needed = n - self.sorted;
+        if needed < unsorted.len() {
+            unsorted.select_nth_unstable_by(needed, cmp);
+        }
+        unsorted[..needed].sort_unstable_by(cmp);
+        self.sorted = n;
+    }
+}
+
+impl<'a> FromIterator<Score<'a>> for Matches<'a> {
+    fn from_iter<I: IntoIterator<Item = Score<'a>>>(iter: I) -> Self {
+        Self::new(iter.into_iter().collect(), 0, Order::default())
+    }
+}
+
+impl<'a> Deref for Matches<'a> {
+    type Target = [Score<'a>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.scores
+    }
+}
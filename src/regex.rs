@@ -0,0 +1,123 @@
+use crate::{line::Line, matcher::Matcher, score::Score};
+use ::regex::RegexBuilder;
+use std::cell::RefCell;
+
+type Compiled = Result<::regex::Regex, String>;
+
+thread_local! {
+    // lines are scored one at a time, so each thread keeps the last query it compiled rather than
+    // compiling it again for every line
+    static COMPILED: RefCell<Option<(Vec<char>, Compiled)>> = const { RefCell::new(None) };
+}
+
+fn compile(query: &[char]) -> Compiled {
+    RegexBuilder::new(&query.iter().collect::<String>())
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| match e {
+            // syntax errors span several lines, pointing at the problem in the pattern; only the
+            // description of the problem fits on one
+            ::regex::Error::Syntax(s) => s
+                .lines()
+                .find_map(|l| l.strip_prefix("error: "))
+                .unwrap_or("invalid syntax")
+                .to_string(),
+            e => e.to_string(),
+        })
+}
+
+fn with_compiled<T>(query: &[char], f: impl FnOnce(&Compiled) -> T) -> T {
+    COMPILED.with(|compiled| {
+        let mut compiled = compiled.borrow_mut();
+        if compiled.as_ref().is_none_or(|(q, _)| q != query) {
+            *compiled = Some((query.to_vec(), compile(query)));
+        }
+        f(&compiled.as_ref().unwrap().1)
+    })
+}
+
+/// Treats the query as a regular expression (ignoring case), matching lines containing a match for
+/// it. Shorter matches rank higher.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Regex;
+
+impl Matcher for Regex {
+    fn score<'a>(&self, line: &'a Line, query: &[char]) -> Option<Score<'a>> {
+        if query.is_empty() {
+            return Some(Score::new(line));
+        }
+        let (start, end) = with_compiled(query, |re| {
+            re.as_ref()
+                .ok()?
+                .find(&line.buf)
+                .map(|m| (m.start(), m.end()))
+        })?;
+        let first = line.buf[..start].chars().count();
+        Some(Score {
+            first,
+            last: first + line.buf[start..end].chars().count(),
+            points: 1,
+            line,
+        })
+    }
+
+    fn validate(&self, query: &[char]) -> Result<(), String> {
+        with_compiled(query, |re| re.as_ref().map(|_| ()).map_err(Clone::clone))
+    }
+
+    // the lines matching one pattern say nothing about which lines match another
+    fn narrows(&self, _query: &str, _other: &str) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    #[test]
+    fn test_score() {
+        let line = Line::from("src/Main.rs");
+        let expected = Some(Score {
+            first: 4,
+            last: 8,
+            points: 1,
+            line: &line,
+        });
+        assert_eq!(Regex.score(&line, &query("m[a-z]+n")), expected);
+        assert_eq!(Regex.score(&line, &query("^main")), None);
+        assert_eq!(Regex.score(&line, &query("(")), None);
+        assert_eq!(Regex.score(&line, &[]), Some(Score::new(&line)));
+        // positions are characters, not bytes
+        let line = Line::from("ééfoo");
+        let score = Regex.score(&line, &query("fo+")).unwrap();
+        assert_eq!((score.first, score.last), (2, 5));
+        assert_eq!(Regex.positions(&line, &query("fo+")), Some(vec![2, 3, 4]));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(Regex.validate(&query("fo+|ba[rz]")), Ok(()));
+        assert_eq!(
+            Regex.validate(&query("foo(")),
+            Err("unclosed group".to_string())
+        );
+        assert!(Regex.validate(&query("[a-")).is_err());
+        assert!(!Regex.narrows("foo", "fo"));
+    }
+
+    #[test]
+    fn test_rank() {
+        let lines = [Line::from("foo_bar"), Line::from("fbar"), Line::from("baz")];
+        let ranked: Vec<&str> = Regex
+            .rank(&lines, "f.*r")
+            .iter()
+            .map(|s| s.line.buf.as_str())
+            .collect();
+        assert_eq!(ranked, vec!["fbar", "foo_bar"]);
+    }
+}
@@ -1,18 +1,133 @@
-use crate::{color::Colors, score::Score};
-use std::{cmp::min, rc::Rc};
+use crate::{color::Colors, selection::Selection, view::View};
+use star::{Matcher, Score};
+use std::{cmp::min, str::FromStr};
 use termion::{clear, color, cursor, style};
 use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
+// where the match counter is shown
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Counter {
+    // before the prompt
+    #[default]
+    Left,
+    // at the right edge of the search line
+    Right,
+    // on a line of its own, below the search line
+    Separate,
+    Hidden,
+}
+
+impl Counter {
+    // the rows the counter takes up besides the search line
+    pub fn rows(self) -> usize {
+        match self {
+            Counter::Separate => 1,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Border {
+    #[default]
+    None,
+    Rounded,
+    Sharp,
+}
+
+impl Border {
+    // the top left, top right, bottom left and bottom right corners, then the horizontal and
+    // vertical lines
+    fn chars(self) -> Option<[char; 6]> {
+        match self {
+            Border::None => None,
+            Border::Rounded => Some(['╭', '╮', '╰', '╯', '─', '│']),
+            Border::Sharp => Some(['┌', '┐', '└', '┘', '─', '│']),
+        }
+    }
+}
+
+// empty rows above and below, and columns either side
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Spacing {
+    pub vertical: usize,
+    pub horizontal: usize,
+}
+
+// either one number for every side, or the rows above and below and then the columns either side,
+// separated by a comma
+impl FromStr for Spacing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |n: &str| {
+            n.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("\"{}\" isn't a number", n))
+        };
+        match s.split_once(',') {
+            Some((vertical, horizontal)) => Ok(Self {
+                vertical: parse(vertical)?,
+                horizontal: parse(horizontal)?,
+            }),
+            None => {
+                let n = parse(s)?;
+                Ok(Self {
+                    vertical: n,
+                    horizontal: n,
+                })
+            }
+        }
+    }
+}
+
+// the rows taken up by the margin, border and padding, half of them above the search line
+pub fn framing_rows(border: Border, margin: Spacing, padding: Spacing) -> usize {
+    let border = if border == Border::None { 0 } else { 1 };
+    2 * (margin.vertical + border + padding.vertical)
+}
+
+// truncates `s` to `width` columns
+fn truncate(s: &str, width: usize) -> String {
+    let mut rv = String::new();
+    let mut rv_width = 0;
+    for c in s.chars() {
+        rv_width += c.width().unwrap_or(0);
+        if rv_width > width {
+            break;
+        }
+        rv.push(c);
+    }
+    rv
+}
+
 pub struct Renderer<'a> {
-    scores: Rc<Vec<Score<'a>>>,
+    scores: &'a View<'a>,
     query: String,
     selected: usize,
+    offset: usize,
     height: usize,
     width: usize,
     fg: &'a Colors,
     bg: &'a Colors,
     match_count_length: usize,
-    tagged: &'a [usize],
+    total: usize,
+    scanning: bool,
+    // reports which characters of each line shown the query matched
+    matcher: Option<&'a dyn Matcher>,
+    header: &'a [String],
+    prompt: &'a str,
+    pointer: &'a str,
+    marker: &'a str,
+    counter: Counter,
+    border: Border,
+    margin: Spacing,
+    padding: Spacing,
+    // the width of the terminal, which `width` is what's left of inside the margin, border and
+    // padding
+    outer_width: usize,
+    tagged: &'a Selection,
+    status: Option<&'a str>,
 }
 
 pub struct RendererConfig<'a> {
@@ -21,100 +136,269 @@ pub struct RendererConfig<'a> {
     pub fg: &'a Colors,
     pub bg: &'a Colors,
     pub match_count_length: usize,
+    // the number of input lines
+    pub total: usize,
+    // lines shown between the search line and the matches
+    pub header: &'a [String],
+    pub prompt: &'a str,
+    // shown before the selected line, if not empty, with every other line indented to match
+    pub pointer: &'a str,
+    // shown before tagged lines
+    pub marker: &'a str,
+    pub counter: Counter,
+    // drawn around everything, inside the margin and outside the padding
+    pub border: Border,
+    pub margin: Spacing,
+    pub padding: Spacing,
 }
 
 impl<'a> Renderer<'a> {
     pub fn new(
         config: &'a RendererConfig,
-        scores: Rc<Vec<Score<'a>>>,
+        scores: &'a View<'a>,
         query: String,
         selected: usize,
-        tagged: &'a [usize],
+        offset: usize,
+        tagged: &'a Selection,
+        status: Option<&'a str>,
     ) -> Self {
+        // the margin and padding are narrowed to fit the terminal, leaving no room inside at worst
+        let border = if config.border == Border::None { 0 } else { 2 };
+        let mut margin = config.margin;
+        margin.horizontal = min(margin.horizontal, config.width.saturating_sub(border) / 2);
+        let mut padding = config.padding;
+        let inside_border = config.width.saturating_sub(2 * margin.horizontal + border);
+        padding.horizontal = min(padding.horizontal, inside_border / 2);
         Self {
             scores,
             query,
             selected,
+            offset,
             match_count_length: config.match_count_length,
+            total: config.total,
+            scanning: false,
+            matcher: None,
             fg: config.fg,
             bg: config.bg,
-            width: config.width,
+            width: inside_border - 2 * padding.horizontal,
             height: config.height,
+            header: config.header,
+            prompt: config.prompt,
+            pointer: config.pointer,
+            marker: config.marker,
+            counter: config.counter,
+            border: config.border,
+            margin,
+            padding,
+            outer_width: config.width,
             tagged,
+            status,
+        }
+    }
+
+    // whether the matches shown are still being added to
+    pub fn scanning(self, scanning: bool) -> Self {
+        Self { scanning, ..self }
+    }
+
+    // the matcher that scored the lines, which highlights just the characters it matched rather
+    // than the whole matched range
+    pub fn matcher(self, matcher: &'a dyn Matcher) -> Self {
+        Self {
+            matcher: Some(matcher),
+            ..self
         }
     }
 
+    fn framing_rows(&self) -> usize {
+        framing_rows(self.border, self.margin, self.padding)
+    }
+
+    // the row of the frame that the search line is on
+    pub fn cursor_row(&self) -> usize {
+        self.framing_rows() / 2
+    }
+
     fn num_visible(&self) -> usize {
-        min(self.height - 1, self.scores.len())
+        min(
+            self.height
+                .saturating_sub(1 + self.counter.rows() + self.header.len() + self.framing_rows()),
+            self.scores.len().saturating_sub(self.offset),
+        )
     }
 
-    fn render_search_line(&self, num_scores: usize) -> String {
-        let line = format!(
-            "{:>width$} > {}",
+    // the number of matches out of the number of lines, followed by the number of tagged lines and
+    // whether scoring is still in progress, if they're worth mentioning
+    fn info(&self, num_scores: usize) -> String {
+        let mut info = format!(
+            "{:>width$}/{}",
             num_scores,
-            self.query,
+            self.total,
             width = self.match_count_length
         );
-        let mut rv = String::with_capacity(line.len());
-        for char in line.chars() {
-            if rv.width() < self.width {
-                rv.push(char);
+        if !self.tagged.is_empty() {
+            info.push_str(&format!(" ({} tagged)", self.tagged.len()));
+        }
+        if self.scanning {
+            info.push_str(" [scanning…]");
+        }
+        info
+    }
+
+    fn render_info_line(&self, num_scores: usize) -> String {
+        format!(
+            "{}{}",
+            truncate(&self.info(num_scores), self.width),
+            clear::UntilNewline
+        )
+    }
+
+    fn render_search_line(&self, num_scores: usize) -> String {
+        let counter = self.info(num_scores);
+        let line = match self.counter {
+            Counter::Left => format!("{} {}{}", counter, self.prompt, self.query),
+            _ => format!("{}{}", self.prompt, self.query),
+        };
+        let mut rv = truncate(&line, self.width);
+        // the status follows the query and the counter can be right-aligned after it, as much of
+        // them as fits, but the cursor has to be left at the end of the query
+        let mut after = self.status.map_or(String::new(), |s| format!("  [{}]", s));
+        if self.counter == Counter::Right {
+            // the last column is left empty, since writing to it can make the terminal wrap
+            let counter = counter.trim_start();
+            let used = rv.width() + after.width() + counter.width() + 1;
+            if used < self.width {
+                after.push_str(&" ".repeat(self.width - used));
+                after.push_str(counter);
             }
         }
+        let after = truncate(&after, self.width - rv.width());
+        rv.push_str(&after);
         rv.push_str(&format!("{}", clear::UntilNewline));
+        if !after.is_empty() {
+            rv.push_str(&format!("{}", cursor::Left(after.width() as u16)));
+        }
+        rv
+    }
+
+    // the pointer's column, if there is a pointer, followed by a space
+    fn pointer_gutter(&self, selected: bool) -> String {
+        match self.pointer.width() {
+            0 => String::new(),
+            _ if selected => format!("{} ", self.pointer),
+            width => " ".repeat(width + 1),
+        }
+    }
+
+    fn render_header_line(&self, line: &str) -> String {
+        // header lines are lined up with the matches below them. tabs are expanded and the line is
+        // truncated to width, as in highlight_line.
+        let gutter = self.pointer_gutter(false);
+        let width = self.width.saturating_sub(gutter.width());
+        let mut rv = format!("{}{}{}", gutter, self.fg.header, self.bg.header);
+        let mut visible_chars: usize = 0;
+        for c in line.chars() {
+            if c == '\t' {
+                let spaces = min(8 - visible_chars % 8, width - visible_chars);
+                rv.push_str(&" ".repeat(spaces));
+                visible_chars += spaces;
+            } else {
+                let char_width = c.width().unwrap_or(0);
+                if visible_chars + char_width > width {
+                    break;
+                }
+                rv.push(c);
+                visible_chars += char_width;
+            }
+            if width <= visible_chars {
+                break;
+            }
+        }
+        rv.push_str(&format!("{}{}", style::Reset, clear::UntilNewline));
+        rv
+    }
+
+    // the style of the parts of a line that aren't matched
+    fn line_style(&self, selected: bool) -> String {
+        let mut rv = format!("{}{}", self.fg.normal, self.bg.normal);
+        if selected {
+            rv.push_str(&format!("{}{}", self.fg.selected, self.bg.selected));
+        }
         rv
     }
 
     fn highlight_line(&self, score: &Score, selected: bool) -> String {
-        // this function highlights matches, expands tabs, and truncates lines to width
+        // this function highlights matches, expands tabs, and truncates lines to the width left
+        // after the pointer and marker
         let mut visible_chars: usize = 0;
-        let tag = if self.tagged.contains(&score.line.index) {
+        let pointer = self.pointer_gutter(selected);
+        let tagged = self.tagged.contains(score.line.index);
+        let marker_width = if tagged { self.marker.width() + 2 } else { 0 };
+        let width = self.width.saturating_sub(pointer.width() + marker_width);
+        let tag = if tagged {
             format!(
-                "{}{} + {}{}",
+                "{}{} {} {}",
                 self.fg.tag,
                 self.bg.tag,
-                color::Fg(color::Reset),
-                color::Bg(color::Reset)
+                self.marker,
+                style::Reset
             )
         } else {
             String::from("")
         };
-        let mut rv = format!("{}{}{}", tag, self.fg.normal, self.bg.normal);
-        if selected {
-            rv.push_str(&format!("{}{}", self.fg.selected, self.bg.selected));
-        }
+        let mut rv = format!("{}{}{}", pointer, tag, self.line_style(selected));
+        let matched = if selected {
+            format!("{}{}", self.fg.matched_selected, self.bg.matched_selected)
+        } else {
+            format!("{}{}", self.fg.matched, self.bg.matched)
+        };
+        let positions = match self.matcher {
+            Some(matcher) => {
+                let query: Vec<char> = self.query.chars().collect();
+                matcher.positions(score.line, &query).unwrap_or_default()
+            }
+            None => (score.first..score.last).collect(),
+        };
+        let mut positions = positions.into_iter().peekable();
+        let mut was_in_match = false;
+        // the style the input gave the line with --ansi, which is layered between the line's own
+        // style and the match highlighting
+        let mut input_style = "";
+        let mut input_styles = score.line.styles().iter().peekable();
         for (i, c) in score.line.buf.chars().enumerate() {
-            if score.first != score.last {
-                if score.first == i {
-                    if selected {
-                        rv.push_str(&format!(
-                            "{}{}",
-                            self.fg.matched_selected, self.bg.matched_selected
-                        ));
-                    } else {
-                        rv.push_str(&format!("{}{}", self.fg.matched, self.bg.matched));
-                    }
-                } else if score.last == i {
-                    if selected {
-                        rv.push_str(&format!("{}{}", self.fg.selected, self.bg.selected));
-                    } else {
-                        rv.push_str(&format!("{}{}", self.fg.normal, self.bg.normal));
-                    }
-                }
+            let mut restyled = false;
+            while let Some((_, style)) = input_styles.next_if(|(at, _)| *at == i) {
+                input_style = style;
+                restyled = true;
+            }
+            let in_match = positions.next_if_eq(&i).is_some();
+            if restyled || (was_in_match && !in_match) {
+                // the matched style may have added attributes (e.g. bold), which only a reset
+                // takes away
+                rv.push_str(&format!(
+                    "{}{}{}",
+                    style::Reset,
+                    self.line_style(selected),
+                    input_style
+                ));
+            }
+            if in_match && (restyled || !was_in_match) {
+                rv.push_str(&matched);
             }
+            was_in_match = in_match;
             if c == '\t' {
                 loop {
                     rv.push(' ');
                     visible_chars += 1;
-                    if visible_chars % 8 == 0 || visible_chars >= self.width {
+                    if visible_chars.is_multiple_of(8) || visible_chars >= width {
                         break;
                     }
                 }
-            } else if self.width > visible_chars {
+            } else if visible_chars + c.width().unwrap_or(0) <= width {
                 rv.push(c);
                 visible_chars += c.width().unwrap_or(0);
             }
-            if self.width <= visible_chars {
+            if width <= visible_chars {
                 break;
             }
         }
@@ -128,39 +412,103 @@ impl<'a> Renderer<'a> {
         rv
     }
 
-    fn render_lines(&self) -> Vec<String> {
-        let mut lines: Vec<String> = vec!["".to_string()]; // to account for search line
-        for (i, score) in self.scores.iter().enumerate().take(self.num_visible()) {
+    pub fn render(&self) -> Vec<String> {
+        let mut lines = vec![self.render_search_line(self.scores.len())];
+        if self.counter == Counter::Separate {
+            lines.push(self.render_info_line(self.scores.len()));
+        }
+        for line in self.header {
+            lines.push(self.render_header_line(line));
+        }
+        for (i, score) in self
+            .scores
+            .iter()
+            .enumerate()
+            .skip(self.offset)
+            .take(self.num_visible())
+        {
             lines.push(self.highlight_line(score, self.selected == i));
         }
-        lines
+        self.frame(lines)
     }
 
-    pub fn render(&self) -> String {
-        let mut output = self.render_lines().join("\r\n");
-        output.push_str(&format!("{}", clear::AfterCursor));
-        if self.num_visible() > 0 {
-            output.push_str(&format!("{}", cursor::Up(self.num_visible() as u16)));
+    // surrounds the lines with the padding, border and margin. a border stays where it is however
+    // many matches there are, so it takes up the whole height.
+    fn frame(&self, mut lines: Vec<String>) -> Vec<String> {
+        let chars = self.border.chars();
+        let no_spacing = self.margin == Spacing::default() && self.padding == Spacing::default();
+        if chars.is_none() && no_spacing {
+            return lines;
         }
-        output.push('\r');
-        output.push_str(&self.render_search_line(self.scores.len()));
-        output
-    }
-
-    pub fn clear() -> String {
-        format!("\r{}", clear::AfterCursor)
+        if chars.is_some() {
+            lines.resize(
+                self.height.saturating_sub(self.framing_rows()),
+                format!("{}", clear::UntilNewline),
+            );
+        }
+        let blank = format!("{}", clear::UntilNewline);
+        let margin = " ".repeat(self.margin.horizontal);
+        let padding = " ".repeat(self.padding.horizontal);
+        let inside = self.width + 2 * self.padding.horizontal;
+        // the right side of the border is put in place with an absolute cursor movement, since
+        // lines can be narrower than the space inside it. the search line is restored to where
+        // it leaves the cursor afterwards. rows are cleared before anything is drawn in the last
+        // column, since clearing after it would erase it.
+        let right_column = self.outer_width - self.margin.horizontal;
+        let (left, right) = match chars {
+            Some([_, _, _, _, _, v]) => (
+                format!("{}{}", v, padding),
+                format!("\x1b[{}G{}", right_column, v),
+            ),
+            None => (padding.clone(), String::new()),
+        };
+        let mut rv = vec![blank.clone(); self.margin.vertical];
+        if let Some([tl, tr, _, _, h, _]) = chars {
+            let top: String = std::iter::repeat_n(h, inside).collect();
+            rv.push(format!("{}{}{}{}{}", blank, margin, tl, top, tr));
+        }
+        let padding_row = format!("{}{}{}{}", margin, left, blank, right);
+        rv.extend(std::iter::repeat_n(
+            padding_row.clone(),
+            self.padding.vertical,
+        ));
+        for (i, line) in lines.into_iter().enumerate() {
+            if i == 0 && !right.is_empty() {
+                rv.push(format!(
+                    "{}{}{}{}{}{}",
+                    margin,
+                    left,
+                    line,
+                    cursor::Save,
+                    right,
+                    cursor::Restore
+                ));
+            } else {
+                rv.push(format!("{}{}{}{}", margin, left, line, right));
+            }
+        }
+        rv.extend(std::iter::repeat_n(padding_row, self.padding.vertical));
+        if let Some([_, _, bl, br, h, _]) = chars {
+            let bottom: String = std::iter::repeat_n(h, inside).collect();
+            rv.push(format!("{}{}{}{}{}", blank, margin, bl, bottom, br));
+        }
+        rv.extend(std::iter::repeat_n(blank, self.margin.vertical));
+        rv
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{color::get_colors, line::Line, score::calculate_score};
-    use clap;
+    use crate::{
+        color::{build_colors, Depth},
+        matches::Matches,
+    };
+    use star::{Line, Matcher, Order, Selecta};
+    use std::sync::Arc;
 
     fn colors() -> (Colors, Colors) {
-        let matches = clap::ArgMatches::new();
-        get_colors(&matches).unwrap()
+        build_colors(&Default::default(), Depth::Ansi256, false).unwrap()
     }
 
     fn config<'a>(colors: &'a (Colors, Colors)) -> RendererConfig<'a> {
@@ -170,6 +518,15 @@ mod tests {
             fg: &colors.0,
             bg: &colors.1,
             match_count_length: 5,
+            total: 99999,
+            header: &[],
+            prompt: "> ",
+            pointer: "",
+            marker: "+",
+            counter: Counter::Left,
+            border: Border::None,
+            margin: Spacing::default(),
+            padding: Spacing::default(),
         }
     }
 
@@ -177,32 +534,279 @@ mod tests {
     fn test_render_search_line() {
         let colors = colors();
         let config = config(&colors);
-        let mut r = Renderer::new(&config, Rc::new(vec![]), String::from("foobar"), 0, &[]);
-        let expected = format!("12345 > foobar{}", clear::UntilNewline);
+        let tagged = Selection::new(None);
+        let mut two_tagged = Selection::new(None);
+        two_tagged.tag_all([4, 2]);
+        let view = View::default();
+        let mut r = Renderer::new(&config, &view, String::from("foobar"), 0, 0, &tagged, None);
+        let expected = format!("12345/99999 > foobar{}", clear::UntilNewline);
         assert_eq!(r.render_search_line(12345), expected);
 
         // test score number justification
-        let expected = format!("  123 > foobar{}", clear::UntilNewline);
+        let expected = format!("  123/99999 > foobar{}", clear::UntilNewline);
         assert_eq!(r.render_search_line(123), expected);
 
         // test line is truncated if necessary
-        r.width = 11;
-        let expected = format!("  123 > foo{}", clear::UntilNewline);
+        r.width = 17;
+        let expected = format!("  123/99999 > foo{}", clear::UntilNewline);
+        assert_eq!(r.render_search_line(123), expected);
+
+        // a status follows the query, leaving the cursor after the query
+        r.status = Some("regex");
+        r.width = 30;
+        let expected = format!(
+            "  123/99999 > foobar  [regex]{}{}",
+            clear::UntilNewline,
+            cursor::Left(9)
+        );
+        assert_eq!(r.render_search_line(123), expected);
+        // and is truncated like the rest of the line
+        r.width = 24;
+        let expected = format!(
+            "  123/99999 > foobar  [r{}{}",
+            clear::UntilNewline,
+            cursor::Left(4)
+        );
+        assert_eq!(r.render_search_line(123), expected);
+        r.width = 20;
+        let expected = format!("  123/99999 > foobar{}", clear::UntilNewline);
+        assert_eq!(r.render_search_line(123), expected);
+
+        // the number of tagged lines follows the number of matches, then whether scoring is
+        // still in progress
+        r.tagged = &two_tagged;
+        r.status = None;
+        r.width = 50;
+        let expected = format!("  123/99999 (2 tagged) > foobar{}", clear::UntilNewline);
+        assert_eq!(r.render_search_line(123), expected);
+        r.scanning = true;
+        let expected = format!(
+            "  123/99999 (2 tagged) [scanning…] > foobar{}",
+            clear::UntilNewline
+        );
+        assert_eq!(r.render_search_line(123), expected);
+    }
+
+    #[test]
+    fn test_render_search_line_counter() {
+        let colors = colors();
+        let mut config = config(&colors);
+        config.prompt = "❯ ";
+        let tagged = Selection::new(None);
+        let view = View::default();
+        let mut r = Renderer::new(&config, &view, String::from("foo"), 0, 0, &tagged, None);
+        let expected = format!("  123/99999 ❯ foo{}", clear::UntilNewline);
+        assert_eq!(r.render_search_line(123), expected);
+        r.counter = Counter::Hidden;
+        let expected = format!("❯ foo{}", clear::UntilNewline);
+        assert_eq!(r.render_search_line(123), expected);
+        // a right-aligned counter stops short of the last column, leaving the cursor after the
+        // query
+        r.counter = Counter::Right;
+        let expected = format!(
+            "❯ foo{}123/99999{}{}",
+            " ".repeat(5),
+            clear::UntilNewline,
+            cursor::Left(14)
+        );
         assert_eq!(r.render_search_line(123), expected);
+        // and follows the status, unless it doesn't fit
+        r.status = Some("regex");
+        r.width = 30;
+        let expected = format!(
+            "❯ foo  [regex]{}123/99999{}{}",
+            " ".repeat(6),
+            clear::UntilNewline,
+            cursor::Left(24)
+        );
+        assert_eq!(r.render_search_line(123), expected);
+        r.width = 20;
+        let expected = format!("❯ foo  [regex]{}{}", clear::UntilNewline, cursor::Left(9));
+        assert_eq!(r.render_search_line(12345), expected);
+        // a separate counter gets a line of its own
+        r.counter = Counter::Separate;
+        r.status = None;
+        assert_eq!(
+            r.render_search_line(123),
+            format!("❯ foo{}", clear::UntilNewline)
+        );
+        let expected = format!("  123/99999{}", clear::UntilNewline);
+        assert_eq!(r.render_info_line(123), expected);
+    }
+
+    #[test]
+    fn test_render() {
+        let tagged = Selection::new(None);
+        let colors = colors();
+        let mut config = config(&colors);
+        config.height = 3;
+        let lines: Vec<Line> = ["foo", "bar", "baz", "qux"]
+            .iter()
+            .enumerate()
+            .map(|(i, l)| Line::new(l.to_string(), i))
+            .collect();
+        let scores: Arc<Matches> = Arc::new(
+            lines
+                .iter()
+                .filter_map(|l| Selecta::default().score(l, &[]))
+                .collect(),
+        );
+        let view = View::new(Arc::clone(&scores), Order::default());
+        let r = Renderer::new(&config, &view, String::new(), 2, 1, &tagged, None);
+        // only the lines in the window starting at the offset are rendered
+        let expected = vec![
+            r.render_search_line(4),
+            r.highlight_line(&scores[1], false),
+            r.highlight_line(&scores[2], true),
+        ];
+        assert_eq!(r.render(), expected);
+        // an offset near the end of the matches renders fewer lines
+        let r = Renderer::new(&config, &view, String::new(), 3, 3, &tagged, None);
+        assert_eq!(r.render().len(), 2);
+        // header lines come between the search line and the matches, taking up their rows
+        let header = [String::from("NAME")];
+        config.height = 4;
+        config.header = &header;
+        let r = Renderer::new(&config, &view, String::new(), 0, 0, &tagged, None);
+        let expected = vec![
+            r.render_search_line(4),
+            r.render_header_line("NAME"),
+            r.highlight_line(&scores[0], true),
+            r.highlight_line(&scores[1], false),
+        ];
+        assert_eq!(r.render(), expected);
+        // as does a separate counter
+        config.counter = Counter::Separate;
+        let r = Renderer::new(&config, &view, String::new(), 0, 0, &tagged, None);
+        let expected = vec![
+            r.render_search_line(4),
+            r.render_info_line(4),
+            r.render_header_line("NAME"),
+            r.highlight_line(&scores[0], true),
+        ];
+        assert_eq!(r.render(), expected);
+        // leaving no room for any matches
+        config.height = 1;
+        let r = Renderer::new(&config, &view, String::new(), 0, 0, &tagged, None);
+        assert_eq!(r.num_visible(), 0);
+    }
+
+    #[test]
+    fn test_render_border() {
+        let tagged = Selection::new(None);
+        let colors = colors();
+        let mut config = config(&colors);
+        config.width = 12;
+        config.height = 5;
+        config.border = Border::Sharp;
+        config.margin = Spacing {
+            vertical: 0,
+            horizontal: 1,
+        };
+        let lines = [Line::new("foo".to_string(), 0)];
+        let scores: Arc<Matches> = Arc::new(
+            lines
+                .iter()
+                .filter_map(|l| Selecta::default().score(l, &[]))
+                .collect(),
+        );
+        let view = View::new(Arc::clone(&scores), Order::default());
+        let r = Renderer::new(&config, &view, String::new(), 0, 0, &tagged, None);
+        // the lines inside are rendered to the width left inside the margin and border
+        assert_eq!(r.width, 8);
+        assert_eq!(r.cursor_row(), 1);
+        let right = "\x1b[11G│";
+        let clear = format!("{}", clear::UntilNewline);
+        let expected = vec![
+            format!("{} ┌────────┐", clear),
+            format!(
+                " │{}{}{}{}",
+                r.render_search_line(1),
+                cursor::Save,
+                right,
+                cursor::Restore
+            ),
+            format!(" │{}{}", r.highlight_line(&scores[0], true), right),
+            // the border takes up the whole height, however few matches there are
+            format!(" │{}{}", clear, right),
+            format!("{} └────────┘", clear),
+        ];
+        assert_eq!(r.render(), expected);
+        // padding goes inside the border, and takes rows from the matches
+        config.padding = Spacing {
+            vertical: 1,
+            horizontal: 0,
+        };
+        config.height = 6;
+        let r = Renderer::new(&config, &view, String::new(), 0, 0, &tagged, None);
+        assert_eq!(r.cursor_row(), 2);
+        assert_eq!(r.num_visible(), 1);
+        let rendered = r.render();
+        assert_eq!(rendered.len(), 6);
+        assert_eq!(rendered[1], format!(" │{}{}", clear, right));
+        // a margin wider than the terminal is narrowed, leaving no room inside the border
+        config.margin = Spacing {
+            vertical: 0,
+            horizontal: 200,
+        };
+        let r = Renderer::new(&config, &view, String::new(), 0, 0, &tagged, None);
+        assert_eq!(r.width, 0);
+        assert_eq!(r.render()[0], format!("{}     ┌┐", clear));
+    }
+
+    #[test]
+    fn test_parse_spacing() {
+        let spacing = |vertical, horizontal| Spacing {
+            vertical,
+            horizontal,
+        };
+        assert_eq!("2".parse(), Ok(spacing(2, 2)));
+        assert_eq!("1,3".parse(), Ok(spacing(1, 3)));
+        assert_eq!("1, 3".parse(), Ok(spacing(1, 3)));
+        assert!("x".parse::<Spacing>().is_err());
+        assert!("1,2,3".parse::<Spacing>().is_err());
+    }
+
+    #[test]
+    fn test_render_header_line() {
+        let tagged = Selection::new(None);
+        let colors = colors();
+        let mut config = config(&colors);
+        config.width = 10;
+        let view = View::default();
+        let r = Renderer::new(&config, &view, String::new(), 0, 0, &tagged, None);
+        let header = |text: &str| {
+            format!(
+                "{}{}{}{}",
+                colors.0.header,
+                text,
+                style::Reset,
+                clear::UntilNewline
+            )
+        };
+        assert_eq!(r.render_header_line("PID CMD"), header("PID CMD"));
+        // tabs are expanded and the line is truncated to width
+        assert_eq!(r.render_header_line("PID\tCOMMAND"), header("PID     CO"));
+        assert_eq!(r.render_header_line("USER COMMAND"), header("USER COMMA"));
     }
 
     #[test]
     fn test_highlight_line() {
         let colors = colors();
         let config = config(&colors);
-        let mut r = Renderer::new(&config, Rc::new(vec![]), String::from("foobar"), 0, &[]);
-        let line = Line::from("foobarbaz");
-        let score = calculate_score(&line, &['b', 'a', 'r']).unwrap();
+        let tagged = Selection::new(None);
+        let mut first_tagged = Selection::new(None);
+        first_tagged.tag(0);
+        let view = View::default();
+        let mut r = Renderer::new(&config, &view, String::from("foobar"), 0, 0, &tagged, None);
+        let line = Line::new("foobarbaz".to_string(), 0);
+        let score = Selecta::default().score(&line, &['b', 'a', 'r']).unwrap();
         let expected = format!(
-            "{}{}foo{}bar{}{}baz{}{}{}{}",
+            "{}{}foo{}bar{}{}{}baz{}{}{}{}",
             color::Fg(color::Reset),
             color::Bg(color::Reset),
             color::Fg(color::Red),
+            style::Reset,
             color::Fg(color::Reset),
             color::Bg(color::Reset),
             color::Fg(color::Reset),
@@ -214,12 +818,15 @@ mod tests {
 
         // test highlighting the selected line
         let expected = format!(
-            "{}{}{}{}foo{}bar{}{}baz{}{}{}{}",
+            "{}{}{}{}foo{}bar{}{}{}{}{}baz{}{}{}{}",
             color::Fg(color::Reset),
             color::Bg(color::Reset),
             color::Fg(color::Reset),
             style::Invert,
             color::Fg(color::Red),
+            style::Reset,
+            color::Fg(color::Reset),
+            color::Bg(color::Reset),
             color::Fg(color::Reset),
             style::Invert,
             color::Fg(color::Reset),
@@ -232,10 +839,11 @@ mod tests {
         // test truncation
         r.width = 7;
         let expected = format!(
-            "{}{}foo{}bar{}{}b{}{}{}{}",
+            "{}{}foo{}bar{}{}{}b{}{}{}{}",
             color::Fg(color::Reset),
             color::Bg(color::Reset),
             color::Fg(color::Red),
+            style::Reset,
             color::Fg(color::Reset),
             color::Bg(color::Reset),
             color::Fg(color::Reset),
@@ -247,8 +855,8 @@ mod tests {
 
         // test tab expansion
         r.width = 100;
-        let line = Line::from("f\too\tbar");
-        let score = calculate_score(&line, &['b', 'a', 'r']).unwrap();
+        let line = Line::new("f\too\tbar".to_string(), 0);
+        let score = Selecta::default().score(&line, &['b', 'a', 'r']).unwrap();
         let expected = format!(
             "{}{}f       oo      {}bar{}{}{}{}",
             color::Fg(color::Reset),
@@ -264,8 +872,8 @@ mod tests {
 
         // test tab expansion and truncation
         r.width = 4;
-        let line = Line::from("foo\tbar");
-        let score = calculate_score(&line, &['b', 'a', 'r']).unwrap();
+        let line = Line::new("foo\tbar".to_string(), 0);
+        let score = Selecta::default().score(&line, &['b', 'a', 'r']).unwrap();
         let expected = format!(
             "{}{}foo {}{}{}{}",
             color::Fg(color::Reset),
@@ -279,18 +887,18 @@ mod tests {
 
         // test tagging
         r.width = config.width;
-        r.tagged = &[9];
-        let line = Line::from("foobarbaz");
-        let score = calculate_score(&line, &['b', 'a', 'r']).unwrap();
+        r.tagged = &first_tagged;
+        let line = Line::new("foobarbaz".to_string(), 0);
+        let score = Selecta::default().score(&line, &['b', 'a', 'r']).unwrap();
         let expected = format!(
-            "{}{} + {}{}{}{}foo{}bar{}{}baz{}{}{}{}",
+            "{}{} + {}{}{}foo{}bar{}{}{}baz{}{}{}{}",
             colors.0.tag,
             colors.1.tag,
-            color::Fg(color::Reset),
-            color::Bg(color::Reset),
+            style::Reset,
             color::Fg(color::Reset),
             color::Bg(color::Reset),
             color::Fg(color::Red),
+            style::Reset,
             color::Fg(color::Reset),
             color::Bg(color::Reset),
             color::Fg(color::Reset),
@@ -300,4 +908,76 @@ mod tests {
         );
         assert_eq!(r.highlight_line(&score, false), expected);
     }
+
+    #[test]
+    fn test_highlight_line_positions() {
+        let colors = colors();
+        let config = config(&colors);
+        let tagged = Selection::new(None);
+        let view = View::default();
+        let selecta = Selecta::default();
+        let r = Renderer::new(&config, &view, String::from("fb"), 0, 0, &tagged, None)
+            .matcher(&selecta);
+        let line = Line::new("foo_bar".to_string(), 0);
+        let score = selecta.score(&line, &['f', 'b']).unwrap();
+        let normal = format!("{}{}", color::Fg(color::Reset), color::Bg(color::Reset));
+        let red = color::Fg(color::Red);
+        // only the matched characters are highlighted, not those between them
+        let expected = format!(
+            "{normal}{red}f{reset}{normal}oo_{red}b{reset}{normal}ar{normal}{reset}{clear}",
+            reset = style::Reset,
+            clear = clear::UntilNewline,
+        );
+        assert_eq!(r.highlight_line(&score, false), expected);
+    }
+
+    #[test]
+    fn test_highlight_line_ansi() {
+        let colors = colors();
+        let config = config(&colors);
+        let tagged = Selection::new(None);
+        let view = View::default();
+        let r = Renderer::new(&config, &view, String::new(), 0, 0, &tagged, None);
+        let line = Line::with_ansi("\x1b[34mfoo\x1b[1mbar\x1b[0mbaz", 0);
+        let score = Selecta::default().score(&line, &['o', 'b']).unwrap();
+        let normal = format!("{}{}", color::Fg(color::Reset), color::Bg(color::Reset));
+        let red = color::Fg(color::Red);
+        // the input's styles are restored after resets, and matches are highlighted on top of them
+        let expected = format!(
+            "{normal}{reset}{normal}\x1b[34mfo{red}o{reset}{normal}\x1b[34m\x1b[1m{red}b\
+             {reset}{normal}\x1b[34m\x1b[1mar{reset}{normal}baz{normal}{reset}{clear}",
+            reset = style::Reset,
+            clear = clear::UntilNewline,
+        );
+        assert_eq!(r.highlight_line(&score, false), expected);
+    }
+
+    #[test]
+    fn test_highlight_line_pointer_and_marker() {
+        let colors = colors();
+        let mut config = config(&colors);
+        config.width = 8;
+        config.pointer = "▶";
+        config.marker = "✓";
+        let mut tagged = Selection::new(None);
+        tagged.tag(0);
+        let view = View::default();
+        let r = Renderer::new(&config, &view, String::new(), 0, 0, &tagged, None);
+        let tagged_line = Line::new("foobarbaz".to_string(), 0);
+        let line = Line::new("foobarbaz".to_string(), 1);
+        let score = Selecta::default().score(&line, &[]).unwrap();
+        // every line leaves room for the pointer, and is truncated to the width left after it
+        let rendered = r.highlight_line(&score, false);
+        assert!(rendered.starts_with(&format!("  {}", colors.0.normal)));
+        assert!(rendered.contains("foobar") && !rendered.contains("foobarb"));
+        let rendered = r.highlight_line(&score, true);
+        assert!(rendered.starts_with("▶ "));
+        // tagged lines are marked, leaving even less room
+        let score = Selecta::default().score(&tagged_line, &[]).unwrap();
+        let rendered = r.highlight_line(&score, true);
+        assert!(rendered.starts_with(&format!("▶ {} ✓ ", colors.0.tag)));
+        assert!(rendered.contains("foo") && !rendered.contains("foob"));
+        // header lines are lined up with the matches
+        assert!(r.render_header_line("NAME").starts_with("  "));
+    }
 }
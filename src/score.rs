@@ -1,14 +1,37 @@
 use crate::line::Line;
-use std::cmp::Ordering;
+use std::{
+    cell::RefCell,
+    cmp::{min, Ordering},
+    mem,
+    str::FromStr,
+};
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum MatchKind {
     Sequential,
     Boundary,
     Normal,
 }
 
-#[derive(Debug, PartialEq)]
+// under the path scheme, an alignment starting outside the basename costs an extra point, as does
+// every two levels of directories above it
+const DIRECTORY_PENALTY: usize = 1;
+const LEVELS_PER_DEPTH_POINT: usize = 2;
+
+/// How lines are interpreted when scoring.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Scheme {
+    /// Lines are plain text.
+    #[default]
+    Default,
+    /// Lines are file paths: camelCase transitions also count as word boundaries, matches in the
+    /// basename beat matches in the directories, and deep paths rank below shallow ones.
+    Path,
+}
+
+/// How well a line matches a query. Lower `points` are better; `first` and `last` are the
+/// character positions delimiting the matched portion of the line.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Score<'a> {
     pub first: usize,
     pub last: usize,
@@ -17,7 +40,7 @@ pub struct Score<'a> {
 }
 
 impl<'a> Score<'a> {
-    fn new(line: &'a Line) -> Self {
+    pub(crate) fn new(line: &'a Line) -> Self {
         Self {
             first: 0,
             last: 0,
@@ -30,101 +53,389 @@ impl<'a> Score<'a> {
         self.last - self.first
     }
 
+    /// Orders scores best first, breaking ties with the default [`Tiebreaks`].
     pub fn cmp(a: &Score, b: &Score) -> Ordering {
-        if a.points == b.points {
-            if a.range_length() == b.range_length() {
-                a.line.len().cmp(&b.line.len())
-            } else {
-                a.range_length().cmp(&b.range_length())
-            }
-        } else {
-            a.points.cmp(&b.points)
+        Tiebreaks::default().cmp(a, b)
+    }
+}
+
+/// A criterion for ordering scores with equal points and match lengths.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tiebreak {
+    /// The shorter line wins.
+    Length,
+    /// The match starting earlier in the line wins.
+    Begin,
+    /// The match ending closer to the end of the line wins.
+    End,
+    /// The line appearing earlier in the input wins.
+    Index,
+}
+
+impl Tiebreak {
+    fn cmp(self, a: &Score, b: &Score) -> Ordering {
+        let from_end = |s: &Score| s.line.low_char_vec.len() - s.last;
+        match self {
+            Tiebreak::Length => a.line.len().cmp(&b.line.len()),
+            Tiebreak::Begin => a.first.cmp(&b.first),
+            Tiebreak::End => from_end(a).cmp(&from_end(b)),
+            Tiebreak::Index => a.line.index.cmp(&b.line.index),
+        }
+    }
+}
+
+/// How scores are ordered: by points, then by the length of the match, then by each tiebreak in
+/// turn. Any ties left over are broken by input order, so the ordering is always reproducible.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tiebreaks {
+    order: [Tiebreak; 4],
+    len: usize,
+}
+
+impl Tiebreaks {
+    /// Returns `None` if `order` is empty or repeats a tiebreak.
+    pub fn new(order: &[Tiebreak]) -> Option<Self> {
+        if order.is_empty() || (1..order.len()).any(|i| order[..i].contains(&order[i])) {
+            return None;
+        }
+        // there are only four tiebreaks, so a list without repeats always fits
+        let mut tiebreaks = Self {
+            order: [Tiebreak::Index; 4],
+            len: order.len(),
+        };
+        tiebreaks.order[..order.len()].copy_from_slice(order);
+        Some(tiebreaks)
+    }
+
+    /// Orders scores best first.
+    pub fn cmp(&self, a: &Score, b: &Score) -> Ordering {
+        a.points
+            .cmp(&b.points)
+            .then_with(|| a.range_length().cmp(&b.range_length()))
+            .then_with(|| {
+                self.order[..self.len]
+                    .iter()
+                    .map(|t| t.cmp(a, b))
+                    .find(|o| o.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            })
+            .then_with(|| Tiebreak::Index.cmp(a, b))
+    }
+}
+
+impl Default for Tiebreaks {
+    fn default() -> Self {
+        Self::new(&[Tiebreak::Length]).unwrap()
+    }
+}
+
+/// How matches are ordered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Order {
+    /// Best first, according to the tiebreaks.
+    Score(Tiebreaks),
+    /// In input order, regardless of how well each line matches.
+    Input,
+}
+
+impl Order {
+    pub fn cmp(&self, a: &Score, b: &Score) -> Ordering {
+        match self {
+            Order::Score(tiebreaks) => tiebreaks.cmp(a, b),
+            Order::Input => Tiebreak::Index.cmp(a, b),
         }
     }
 }
 
+impl Default for Order {
+    fn default() -> Self {
+        Order::Score(Tiebreaks::default())
+    }
+}
+
+impl FromStr for Tiebreaks {
+    type Err = String;
+
+    /// Parses a comma-separated list of tiebreaks, e.g. "begin,length".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let order = s
+            .split(',')
+            .map(|t| match t.trim() {
+                "length" => Ok(Tiebreak::Length),
+                "begin" => Ok(Tiebreak::Begin),
+                "end" => Ok(Tiebreak::End),
+                "index" => Ok(Tiebreak::Index),
+                t => Err(format!("unknown tiebreak: \"{}\"", t)),
+            })
+            .collect::<Result<Vec<Tiebreak>, String>>()?;
+        Tiebreaks::new(&order).ok_or_else(|| format!("repeated tiebreak in \"{}\"", s))
+    }
+}
+
+/// Scores `line` against `query` using Selecta's algorithm, returning `None` if it doesn't match.
+#[cfg(test)]
 pub fn calculate_score<'a>(line: &'a Line, query: &[char]) -> Option<Score<'a>> {
-    match query.len() {
-        0 => Some(Score::new(line)),
-        1 => line
-            .low_buf
-            .find(query[0].to_lowercase().to_string().as_str())
-            .map(|index| Score {
-                line,
-                points: 1,
-                last: index + 1,
-                first: index,
-            }),
-        _ => {
-            let mut score = Score::new(line);
-            let mut found_score = false;
-            for (start, _) in line
-                .low_buf
-                .match_indices(query[0].to_lowercase().to_string().as_str())
-            {
-                if let Some((points, last_index)) = find_end_of_match(line, &query[1..], start) {
-                    found_score = true;
-                    if last_index != 0 && points < score.points {
-                        score.first = start;
-                        score.last = last_index + 1;
-                        score.points = points;
-                    }
+    calculate_score_with(line, query, Scheme::Default)
+}
+
+/// Like [`calculate_score`], interpreting `line` according to `scheme`.
+pub fn calculate_score_with<'a>(
+    line: &'a Line,
+    query: &[char],
+    scheme: Scheme,
+) -> Option<Score<'a>> {
+    if query.is_empty() {
+        return Some(Score::new(line));
+    }
+    let (last, step) = align(line, query, scheme, None)?;
+    let depth = match scheme {
+        Scheme::Default => 0,
+        Scheme::Path => line.buf.trim_end_matches('/').matches('/').count(),
+    };
+    Some(Score {
+        first: step.first,
+        last: last + 1,
+        points: step.points + depth / LEVELS_PER_DEPTH_POINT,
+        line,
+    })
+}
+
+/// Returns the positions of the characters in `line` matched by `query` in the best alignment
+/// found by [`calculate_score`], or `None` if it doesn't match.
+#[cfg(test)]
+pub fn match_positions(line: &Line, query: &[char]) -> Option<Vec<usize>> {
+    match_positions_with(line, query, Scheme::Default)
+}
+
+/// Like [`match_positions`], interpreting `line` according to `scheme`.
+pub fn match_positions_with(line: &Line, query: &[char], scheme: Scheme) -> Option<Vec<usize>> {
+    if query.is_empty() {
+        return Some(vec![]);
+    }
+    let mut history = vec![];
+    let (last, mut step) = align(line, query, scheme, Some(&mut history))?;
+    let mut positions = vec![last];
+    for row in history.iter().rev().skip(1) {
+        positions.push(step.prev);
+        step = row[step.prev][step.prev_kind as usize].unwrap();
+    }
+    positions.reverse();
+    Some(positions)
+}
+
+// the cheapest alignment of the query so far that ends with a match at a given position, reached
+// by a given kind of match
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Step {
+    points: usize,
+    first: usize,
+    // where the previous query character was matched, and how
+    prev: usize,
+    prev_kind: MatchKind,
+}
+
+type Row = Vec<[Option<Step>; 3]>;
+
+thread_local! {
+    // lines are aligned one at a time, so each thread keeps the two rows it aligns with rather than
+    // allocating new ones for every character of the query on every line
+    static ROWS: RefCell<(Row, Row)> = const { RefCell::new((Vec::new(), Vec::new())) };
+}
+
+const MATCH_KINDS: [MatchKind; 3] = [
+    MatchKind::Sequential,
+    MatchKind::Boundary,
+    MatchKind::Normal,
+];
+
+// lower points win, then the earlier match
+fn cheaper(a: Option<Step>, b: Option<Step>) -> Option<Step> {
+    match (a, b) {
+        (Some(a), Some(b)) if (b.points, b.first) < (a.points, a.first) => Some(b),
+        (Some(a), _) => Some(a),
+        (None, b) => b,
+    }
+}
+
+// finds the cheapest way to match the query against the line. matching the first character costs
+// one point. after that, a run of sequential characters costs one point, as does a run of
+// characters following word boundaries; any other character costs the distance from the previous
+// match. since the cost of each match depends on how the previous one was made, the cheapest cost
+// of matching each query character at each position is kept for each kind of match, and every
+// alignment is considered rather than greedily taking the first occurrence of each character.
+//
+// returns the position of the last matched character and the step that matched it. if `history`
+// is given, every row of steps is pushed to it so that the alignment can be traced back.
+fn align(
+    line: &Line,
+    query: &[char],
+    scheme: Scheme,
+    history: Option<&mut Vec<Row>>,
+) -> Option<(usize, Step)> {
+    ROWS.with_borrow_mut(|(row, next)| align_with(line, query, scheme, history, row, next))
+}
+
+fn align_with(
+    line: &Line,
+    query: &[char],
+    scheme: Scheme,
+    mut history: Option<&mut Vec<Row>>,
+    row: &mut Row,
+    next: &mut Row,
+) -> Option<(usize, Step)> {
+    let chars: Vec<char> = line.low_char_vec.iter().map(|t| t.1).collect();
+    let query: Vec<char> = query.iter().flat_map(|c| c.to_lowercase()).collect();
+    let boundaries = boundaries(line, &chars, scheme);
+    let basename = match scheme {
+        Scheme::Default => 0,
+        Scheme::Path => basename_start(&chars),
+    };
+    row.clear();
+    row.extend(chars.iter().enumerate().map(|(j, c)| {
+        let mut steps = [None; 3];
+        if *c == query[0] {
+            steps[MatchKind::Normal as usize] = Some(Step {
+                points: if j < basename {
+                    1 + DIRECTORY_PENALTY
                 } else {
-                    // take this string: "foobarflubfuzz" - if we're searching for "fbar", we'll
-                    // find it in the initial six chars ("foobar"), but won't find it past that.
-                    // if find_end_of_match() returns None, that means it didn't find one of the
-                    // chars it was looking for, so don't bother searching after that. i.e. we'll
-                    // search from the 'f' in "flub", won't find 'a', and thus won't bother
-                    // searching from the 'f' in "fuzz"
-                    break;
+                    1
+                },
+                first: j,
+                prev: j,
+                prev_kind: MatchKind::Normal,
+            });
+        }
+        steps
+    }));
+
+    for q in &query[1..] {
+        if row.iter().all(|steps| steps.iter().all(Option::is_none)) {
+            return None;
+        }
+        next.clear();
+        next.resize(chars.len(), [None; 3]);
+        // the cheapest steps for the previous query character at least two positions back, split
+        // by whether a boundary match would continue a run of them. for normal matches the cost
+        // depends on the distance, so those are compared by points minus position.
+        let mut after_boundary: Option<Step> = None;
+        let mut after_other: Option<Step> = None;
+        let mut before_normal: Option<(isize, Step)> = None;
+        for j in 0..chars.len() {
+            if j >= 2 {
+                let k = j - 2;
+                for kind in MATCH_KINDS {
+                    let step = match row[k][kind as usize] {
+                        Some(step) => Step {
+                            prev: k,
+                            prev_kind: kind,
+                            ..step
+                        },
+                        None => continue,
+                    };
+                    if kind == MatchKind::Boundary {
+                        after_boundary = cheaper(after_boundary, Some(step));
+                    } else {
+                        after_other = cheaper(after_other, Some(step));
+                    }
+                    let key = step.points as isize - k as isize;
+                    before_normal = match before_normal {
+                        Some((best, b)) if (best, b.first) <= (key, step.first) => Some((best, b)),
+                        _ => Some((key, step)),
+                    };
                 }
             }
-
-            if found_score {
-                Some(score)
+            if chars[j] != *q || j == 0 {
+                continue;
+            }
+            let mut sequential = None;
+            for kind in MATCH_KINDS {
+                if let Some(step) = row[j - 1][kind as usize] {
+                    let points = if kind == MatchKind::Sequential { 0 } else { 1 };
+                    sequential = cheaper(
+                        sequential,
+                        Some(Step {
+                            points: step.points + points,
+                            first: step.first,
+                            prev: j - 1,
+                            prev_kind: kind,
+                        }),
+                    );
+                }
+            }
+            next[j][MatchKind::Sequential as usize] = sequential;
+            if boundaries[j] {
+                next[j][MatchKind::Boundary as usize] = cheaper(
+                    after_boundary,
+                    after_other.map(|step| Step {
+                        points: step.points + 1,
+                        ..step
+                    }),
+                );
             } else {
-                None
+                next[j][MatchKind::Normal as usize] = before_normal.map(|(_, step)| Step {
+                    points: step.points + j - step.prev,
+                    ..step
+                });
             }
         }
+        if let Some(history) = history.as_mut() {
+            history.push(row.clone());
+        }
+        mem::swap(row, next);
     }
-}
 
-fn find_end_of_match(line: &Line, chars: &[char], start: usize) -> Option<(usize, usize)> {
-    let mut last_index = start;
-    let mut score = 1;
-    let mut last_match_kind = MatchKind::Normal;
-
-    for c in chars.iter().collect::<String>().to_lowercase().chars() {
-        let index = match line
-            .low_char_vec
-            .iter()
-            .find(|t| t.0 > last_index && c == t.1)
-        {
-            Some(t) => t.0,
-            None => return None,
-        };
+    let mut best: Option<(usize, Step)> = None;
+    for (j, step) in row
+        .iter()
+        .enumerate()
+        .flat_map(|(j, steps)| steps.iter().flatten().map(move |step| (j, *step)))
+    {
+        // positions are visited in order, so only strictly better steps replace the best one,
+        // which makes the earliest end win among equally cheap matches with the same start
+        if best.is_none_or(|(_, b)| (step.points, step.first) < (b.points, b.first)) {
+            best = Some((j, step));
+        }
+    }
+    if let Some(history) = history {
+        history.push(row.clone());
+    }
+    best
+}
 
-        if index == last_index + 1 {
-            if last_match_kind != MatchKind::Sequential {
-                last_match_kind = MatchKind::Sequential;
-                score += 1;
-            }
-        } else if index < line.low_char_vec.len()
-            && !line.low_char_vec[index - 1].1.is_alphanumeric()
-        {
-            if last_match_kind != MatchKind::Boundary {
-                last_match_kind = MatchKind::Boundary;
-                score += 1;
+// whether each character follows a word boundary. under the path scheme, so does an uppercase
+// letter following a lowercase one.
+fn boundaries(line: &Line, chars: &[char], scheme: Scheme) -> Vec<bool> {
+    let mut boundaries: Vec<bool> = (0..chars.len())
+        .map(|j| j > 0 && !chars[j - 1].is_alphanumeric())
+        .collect();
+    if scheme == Scheme::Path {
+        // lowercasing can turn one character into several, so only the first of them is marked
+        let upper: Vec<bool> = line
+            .buf
+            .chars()
+            .flat_map(|c| {
+                let is_upper = c.is_uppercase();
+                c.to_lowercase()
+                    .enumerate()
+                    .map(move |(i, _)| is_upper && i == 0)
+            })
+            .collect();
+        for j in 1..min(chars.len(), upper.len()) {
+            if upper[j] && !upper[j - 1] && chars[j - 1].is_lowercase() {
+                boundaries[j] = true;
             }
-        } else {
-            last_match_kind = MatchKind::Normal;
-            score += index - last_index;
         }
-
-        last_index = index;
     }
+    boundaries
+}
 
-    Some((score, last_index))
+// the position of the first character after the last directory separator, ignoring trailing ones
+fn basename_start(chars: &[char]) -> usize {
+    let end = chars.len() - chars.iter().rev().take_while(|c| **c == '/').count();
+    chars[..end]
+        .iter()
+        .rposition(|c| *c == '/')
+        .map_or(0, |i| i + 1)
 }
 
 #[cfg(test)]
@@ -132,6 +443,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::legacy_numeric_constants)]
     fn test_calculate_score() {
         // test to make sure calculate_score() breaks in else clause
         let line = Line::from("foobarflubfuzz");
@@ -251,27 +563,62 @@ mod tests {
     }
 
     #[test]
-    fn test_find_end_of_match() {
-        // test score is None if query isn't in string
+    fn test_optimal_alignment() {
+        // taking the first 'b' after the 'f' costs 5 points for the distance between them, but the
+        // 'b' after the word boundary further along only costs one
+        let line = Line::from("foo_xbar_bar");
+        let expected = Some(Score {
+            first: 0,
+            last: 10,
+            points: 2,
+            line: &line,
+        });
+        assert_eq!(calculate_score(&line, &['f', 'b']), expected);
+
+        // which means it now ranks above a line with a short, but mid-word, match
+        let lines = [Line::from("fxb"), Line::from("foo_xbar_bar")];
+        let mut scores: Vec<Score> = lines
+            .iter()
+            .filter_map(|l| calculate_score(l, &['f', 'b']))
+            .collect();
+        scores.sort_unstable_by(Score::cmp);
+        assert_eq!(scores[0].line.buf, "foo_xbar_bar");
+        assert_eq!(scores[1].line.buf, "fxb");
+
+        // a later sequential run beats the first occurrences of its characters
+        let line = Line::from("ba_x_r_bar");
+        let expected = Some(Score {
+            first: 7,
+            last: 10,
+            points: 2,
+            line: &line,
+        });
+        assert_eq!(calculate_score(&line, &['b', 'a', 'r']), expected);
+    }
+
+    #[test]
+    fn test_match_positions() {
+        assert_eq!(match_positions(&Line::from("foo"), &['q', 'x', 'z']), None);
+        assert_eq!(match_positions(&Line::from("foo"), &[]), Some(vec![]));
         assert_eq!(
-            find_end_of_match(&Line::from("foo"), &['q', 'x', 'z'], 0),
-            None
+            match_positions(&Line::from("foobar"), &['b', 'a', 'r']),
+            Some(vec![3, 4, 5])
         );
-
-        // test score is None if query isn't in string _after_ specified start
         assert_eq!(
-            find_end_of_match(&Line::from("foofbar"), &['f', 'o'], 3),
-            None
+            match_positions(&Line::from("foo_xbar_bar"), &['f', 'b']),
+            Some(vec![0, 9])
+        );
+        assert_eq!(
+            match_positions(&Line::from("foo / ba r"), &['O', 'R']),
+            Some(vec![1, 9])
+        );
+        assert_eq!(
+            match_positions(
+                &Line::from("f||||||||b||||||||||||||a||||f||||||||r"),
+                &['b', 'a', 'r']
+            ),
+            Some(vec![9, 24, 38])
         );
-
-        // find first score when multiples exist. keep in mind that
-        // find_end_of_match doesn't find the _entire_ match, just the end of
-        // the match given the correct starting point, so whatever `start` value
-        // is provided is assumed to be the start of the match.
-        let s = Line::from("foofoofoobar");
-        assert_eq!(find_end_of_match(&s, &['b'], 0), Some((10, 9)));
-        assert_eq!(find_end_of_match(&s, &['b'], 4), Some((6, 9)));
-        assert_eq!(find_end_of_match(&s, &['b'], 8), Some((2, 9)));
     }
 
     #[test]
@@ -386,4 +733,100 @@ mod tests {
         };
         assert_eq!(Score::cmp(&a, &b), Ordering::Equal);
     }
+
+    #[test]
+    fn test_tiebreaks() {
+        use Tiebreak::*;
+        assert_eq!("length".parse(), Ok(Tiebreaks::default()));
+        assert_eq!(
+            "begin, index".parse(),
+            Ok(Tiebreaks::new(&[Begin, Index]).unwrap())
+        );
+        assert!("length,begin,length".parse::<Tiebreaks>().is_err());
+        assert!("middle".parse::<Tiebreaks>().is_err());
+        assert!("".parse::<Tiebreaks>().is_err());
+        assert_eq!(Tiebreaks::new(&[]), None);
+
+        let lines = [
+            Line::new("xx_foo".to_string(), 0),
+            Line::new("foo_xxxx".to_string(), 1),
+            Line::new("x_foo_x".to_string(), 2),
+            Line::new("foo_xx".to_string(), 3),
+        ];
+        let ranked = |order: &str| -> Vec<usize> {
+            let tiebreaks: Tiebreaks = order.parse().unwrap();
+            let mut scores: Vec<Score> = lines
+                .iter()
+                .filter_map(|l| calculate_score(l, &['f', 'o', 'o']))
+                .collect();
+            scores.sort_unstable_by(|a, b| tiebreaks.cmp(a, b));
+            scores.iter().map(|s| s.line.index).collect()
+        };
+        // every line matches equally well, so only the tiebreaks decide
+        assert_eq!(ranked("length"), vec![0, 3, 2, 1]);
+        assert_eq!(ranked("begin"), vec![1, 3, 2, 0]);
+        assert_eq!(ranked("end"), vec![0, 2, 3, 1]);
+        assert_eq!(ranked("index"), vec![0, 1, 2, 3]);
+        assert_eq!(ranked("begin,length"), vec![3, 1, 2, 0]);
+        // points and match length always come first
+        let lines = [
+            Line::new("f_o_o".to_string(), 0),
+            Line::new("foo".to_string(), 1),
+        ];
+        let mut scores: Vec<Score> = lines
+            .iter()
+            .filter_map(|l| calculate_score(l, &['f', 'o', 'o']))
+            .collect();
+        let tiebreaks = Tiebreaks::new(&[Index]).unwrap();
+        scores.sort_unstable_by(|a, b| tiebreaks.cmp(a, b));
+        assert_eq!(scores[0].line.index, 1);
+    }
+
+    #[test]
+    fn test_path_scheme() {
+        let points = |line: &str, query: &[char], scheme: Scheme| {
+            calculate_score_with(&Line::from(line), query, scheme).map(|s| s.points)
+        };
+        // camelCase transitions are boundaries
+        assert_eq!(points("fooBar", &['f', 'b'], Scheme::Default), Some(4));
+        assert_eq!(points("fooBar", &['f', 'b'], Scheme::Path), Some(2));
+        // matches starting outside the basename cost more
+        assert_eq!(
+            points("lib/main.rs", &['m', 'a', 'i', 'n'], Scheme::Path),
+            Some(2)
+        );
+        assert_eq!(
+            points("main/lib.rs", &['m', 'a', 'i', 'n'], Scheme::Path),
+            Some(3)
+        );
+        // as do deep paths
+        assert_eq!(
+            points("a/b/c/d/main.rs", &['m', 'a', 'i', 'n'], Scheme::Default),
+            Some(2)
+        );
+        assert_eq!(
+            points("a/b/c/d/main.rs", &['m', 'a', 'i', 'n'], Scheme::Path),
+            Some(4)
+        );
+        assert_eq!(points("foo", &['x'], Scheme::Path), None);
+        // the basename is preferred when choosing between alignments
+        let line = Line::from("main/main.rs");
+        assert_eq!(
+            match_positions_with(&line, &['m', 'a', 'i', 'n'], Scheme::Default),
+            Some(vec![0, 1, 2, 3])
+        );
+        assert_eq!(
+            match_positions_with(&line, &['m', 'a', 'i', 'n'], Scheme::Path),
+            Some(vec![5, 6, 7, 8])
+        );
+    }
+
+    #[test]
+    fn test_basename_start() {
+        let chars = |s: &str| s.chars().collect::<Vec<char>>();
+        assert_eq!(basename_start(&chars("main.rs")), 0);
+        assert_eq!(basename_start(&chars("src/main.rs")), 4);
+        assert_eq!(basename_start(&chars("src/main/")), 4);
+        assert_eq!(basename_start(&chars("/")), 0);
+    }
 }
@@ -0,0 +1,155 @@
+use std::cmp::max;
+use termion::{clear, cursor};
+
+#[derive(Debug, Default)]
+pub struct Screen {
+    rows: Vec<String>,
+    // the row of the frame on screen that the cursor rests on
+    cursor_row: usize,
+    // the most rows any frame has had, which is how far the ui has reached down the terminal
+    most_rows: usize,
+}
+
+impl Screen {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn diff(&mut self, frame: Vec<String>, cursor_row: usize) -> String {
+        // the cursor rests on the search line between frames, which is the first row of a frame
+        // unless there's a border or margin above it. the search line is always written last, so
+        // that the cursor ends up where it leaves it; every other row is only rewritten if it
+        // differs from what is already on screen. rows are reached with a bare line feed (no OPOST
+        // in raw mode) rather than cursor::Down so that the terminal scrolls if the ui is drawn at
+        // the bottom of the screen.
+        let mut output = String::new();
+        if self.cursor_row > 0 {
+            output.push_str(&format!("{}", cursor::Up(self.cursor_row as u16)));
+        }
+        if self.rows.is_empty() {
+            output.push_str(&format!("\r{}", clear::AfterCursor));
+        }
+        let num_rows = max(frame.len(), self.rows.len());
+        let last = (0..num_rows)
+            .rev()
+            .find(|&i| i != cursor_row && frame.get(i) != self.rows.get(i))
+            .unwrap_or(0);
+        let bottom = max(last, cursor_row);
+        for i in 0..=bottom {
+            if i > 0 {
+                output.push('\n');
+            }
+            if i == cursor_row {
+                continue;
+            }
+            match (frame.get(i), self.rows.get(i)) {
+                (Some(new), old) if old != Some(new) => {
+                    output.push('\r');
+                    output.push_str(new);
+                }
+                (None, Some(_)) => output.push_str(&format!("\r{}", clear::CurrentLine)),
+                _ => {}
+            }
+        }
+        if bottom > cursor_row {
+            output.push_str(&format!("{}", cursor::Up((bottom - cursor_row) as u16)));
+        }
+        output.push('\r');
+        if let Some(search_line) = frame.get(cursor_row) {
+            output.push_str(search_line);
+        }
+        self.most_rows = max(self.most_rows, frame.len());
+        self.rows = frame;
+        self.cursor_row = cursor_row;
+        output
+    }
+
+    pub fn most_rows(&self) -> usize {
+        self.most_rows
+    }
+
+    pub fn clear(&mut self) -> String {
+        let mut output = String::new();
+        if self.cursor_row > 0 {
+            output.push_str(&format!("{}", cursor::Up(self.cursor_row as u16)));
+        }
+        output.push_str(&format!("\r{}", clear::AfterCursor));
+        self.rows.clear();
+        self.cursor_row = 0;
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(rows: &[&str]) -> Vec<String> {
+        rows.iter().map(|r| r.to_string()).collect()
+    }
+
+    #[test]
+    fn test_diff_first_frame() {
+        let mut screen = Screen::new();
+        let expected = format!(
+            "\r{}\n\rfoo\n\rbar{}\r> f",
+            clear::AfterCursor,
+            cursor::Up(2)
+        );
+        assert_eq!(screen.diff(frame(&["> f", "foo", "bar"]), 0), expected);
+    }
+
+    #[test]
+    fn test_diff_unchanged_rows() {
+        let mut screen = Screen::new();
+        screen.diff(frame(&["> f", "foo", "bar"]), 0);
+        // nothing changed except the search line, so only the search line is written
+        assert_eq!(screen.diff(frame(&["> fo", "foo", "bar"]), 0), "\r> fo");
+        // only the last row changed, so the first is skipped over
+        let expected = format!("\n\n\rbaz{}\r> fo", cursor::Up(2));
+        assert_eq!(screen.diff(frame(&["> fo", "foo", "baz"]), 0), expected);
+    }
+
+    #[test]
+    fn test_diff_removed_rows() {
+        let mut screen = Screen::new();
+        screen.diff(frame(&["> f", "foo", "bar", "baz"]), 0);
+        let expected = format!(
+            "\n\rqux\n\r{}\n\r{}{}\r> fq",
+            clear::CurrentLine,
+            clear::CurrentLine,
+            cursor::Up(3)
+        );
+        assert_eq!(screen.diff(frame(&["> fq", "qux"]), 0), expected);
+        assert_eq!(screen.most_rows(), 4);
+    }
+
+    #[test]
+    fn test_diff_cursor_row() {
+        let mut screen = Screen::new();
+        // rows above the search line are written on the way down to it
+        let expected = format!(
+            "\r{}\r+--\n\n\r| a{}\r| > f",
+            clear::AfterCursor,
+            cursor::Up(1)
+        );
+        assert_eq!(screen.diff(frame(&["+--", "| > f", "| a"]), 1), expected);
+        // the next frame starts from the top again
+        let expected = format!("{}\n\r| > fo", cursor::Up(1));
+        assert_eq!(screen.diff(frame(&["+--", "| > fo", "| a"]), 1), expected);
+        // and clearing starts from the top too
+        let expected = format!("{}\r{}", cursor::Up(1), clear::AfterCursor);
+        assert_eq!(screen.clear(), expected);
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut screen = Screen::new();
+        screen.diff(frame(&["> f", "foo"]), 0);
+        assert_eq!(screen.clear(), format!("\r{}", clear::AfterCursor));
+        // after clearing, the next frame is drawn from scratch
+        assert!(screen
+            .diff(frame(&["> f", "foo"]), 0)
+            .starts_with(&format!("\r{}", clear::AfterCursor)));
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::HashSet;
+
+// the order tagged lines are output in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TaggedOrder {
+    // the order they were tagged in
+    #[default]
+    Tag,
+    // the order they were read in
+    Input,
+    // the order they're ranked in for the current query
+    Score,
+}
+
+// the indices of the tagged lines, in the order they were tagged, never holding more than `limit`
+#[derive(Debug, Default)]
+pub struct Selection {
+    order: Vec<usize>,
+    tagged: HashSet<usize>,
+    limit: Option<usize>,
+}
+
+impl Selection {
+    pub fn new(limit: Option<usize>) -> Self {
+        Self {
+            limit,
+            ..Self::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.tagged.contains(&index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.order.iter().copied()
+    }
+
+    // returns the tagged lines in the given order. `ranking` is every match for the current query,
+    // best first; tagged lines that don't match it follow the ones that do, in tag order
+    pub fn ordered(
+        &self,
+        order: TaggedOrder,
+        ranking: impl IntoIterator<Item = usize>,
+    ) -> Vec<usize> {
+        match order {
+            TaggedOrder::Tag => self.order.clone(),
+            TaggedOrder::Input => {
+                let mut indices = self.order.clone();
+                indices.sort_unstable();
+                indices
+            }
+            TaggedOrder::Score => {
+                let mut indices: Vec<usize> =
+                    ranking.into_iter().filter(|i| self.contains(*i)).collect();
+                let ranked: HashSet<usize> = indices.iter().copied().collect();
+                indices.extend(self.iter().filter(|i| !ranked.contains(i)));
+                indices
+            }
+        }
+    }
+
+    // returns whether the line is tagged, which it can't be if the limit has been reached
+    pub fn tag(&mut self, index: usize) -> bool {
+        if self.contains(index) {
+            return true;
+        }
+        if self.limit.is_some_and(|limit| self.len() >= limit) {
+            return false;
+        }
+        self.tagged.insert(index);
+        self.order.push(index);
+        true
+    }
+
+    pub fn toggle(&mut self, index: usize) {
+        if self.contains(index) {
+            self.untag_all([index]);
+        } else {
+            self.tag(index);
+        }
+    }
+
+    pub fn tag_all(&mut self, indices: impl IntoIterator<Item = usize>) {
+        for index in indices {
+            if !self.tag(index) {
+                break;
+            }
+        }
+    }
+
+    pub fn untag_all(&mut self, indices: impl IntoIterator<Item = usize>) {
+        let untagged: HashSet<usize> = indices
+            .into_iter()
+            .filter(|i| self.tagged.remove(i))
+            .collect();
+        if !untagged.is_empty() {
+            self.order.retain(|i| !untagged.contains(i));
+        }
+    }
+
+    // tags the untagged lines and untags the tagged ones
+    pub fn invert(&mut self, indices: impl IntoIterator<Item = usize>) {
+        let (tagged, untagged): (Vec<usize>, Vec<usize>) =
+            indices.into_iter().partition(|i| self.contains(*i));
+        self.untag_all(tagged);
+        self.tag_all(untagged);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tagged(selection: &Selection) -> Vec<usize> {
+        selection.iter().collect()
+    }
+
+    #[test]
+    fn test_toggle() {
+        let mut s = Selection::new(None);
+        s.toggle(3);
+        s.toggle(1);
+        s.toggle(2);
+        assert_eq!(tagged(&s), vec![3, 1, 2]);
+        // untagging works regardless of the order lines were tagged in
+        s.toggle(1);
+        assert_eq!(tagged(&s), vec![3, 2]);
+        assert!(!s.contains(1));
+        assert!(s.contains(3));
+        s.toggle(3);
+        s.toggle(2);
+        assert!(s.is_empty());
+    }
+
+    #[test]
+    fn test_tag_all_and_untag_all() {
+        let mut s = Selection::new(None);
+        s.tag(5);
+        s.tag_all([1, 5, 2]);
+        assert_eq!(tagged(&s), vec![5, 1, 2]);
+        s.untag_all([5, 7]);
+        assert_eq!(tagged(&s), vec![1, 2]);
+        assert_eq!(s.len(), 2);
+    }
+
+    #[test]
+    fn test_invert() {
+        let mut s = Selection::new(None);
+        s.tag_all([1, 2, 9]);
+        s.invert([1, 2, 3, 4]);
+        assert_eq!(tagged(&s), vec![9, 3, 4]);
+    }
+
+    #[test]
+    fn test_ordered() {
+        let mut s = Selection::new(None);
+        s.tag_all([3, 1, 7, 5]);
+        assert_eq!(s.ordered(TaggedOrder::Tag, []), vec![3, 1, 7, 5]);
+        assert_eq!(s.ordered(TaggedOrder::Input, []), vec![1, 3, 5, 7]);
+        // lines that aren't ranked come last
+        let ranking = [9, 5, 2, 1, 4];
+        assert_eq!(s.ordered(TaggedOrder::Score, ranking), vec![5, 1, 3, 7]);
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut s = Selection::new(Some(2));
+        assert!(s.tag(1));
+        assert!(s.tag(2));
+        assert!(!s.tag(3));
+        // tagging an already tagged line still succeeds
+        assert!(s.tag(1));
+        s.toggle(3);
+        s.tag_all([4, 5]);
+        assert_eq!(tagged(&s), vec![1, 2]);
+        s.invert([2, 3, 4]);
+        assert_eq!(tagged(&s), vec![1, 3]);
+    }
+}
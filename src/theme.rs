@@ -0,0 +1,179 @@
+use crate::color::COLOR_KEYS;
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{Error, ErrorKind, Result},
+    path::PathBuf,
+};
+
+// the built-in themes, as the colors they set
+const THEMES: &[(&str, &[(&str, &str)])] = &[
+    ("default", &[]),
+    (
+        "dark",
+        &[
+            ("matched-fg", "#ffaf5f"),
+            ("selected-fg", "#ffffff"),
+            ("selected-bg", "#3a3a3a"),
+            ("matched-selected-fg", "#ffd75f"),
+            ("tag-fg", "#5fafff"),
+            ("header-fg", "#808080"),
+        ],
+    ),
+    (
+        "light",
+        &[
+            ("matched-fg", "#d70000"),
+            ("selected-fg", "#000000"),
+            ("selected-bg", "#e4e4e4"),
+            ("matched-selected-fg", "#af0000"),
+            ("tag-fg", "#005fd7"),
+            ("header-fg", "#767676"),
+        ],
+    ),
+    (
+        "solarized",
+        &[
+            ("normal-fg", "#839496"),
+            ("matched-fg", "#cb4b16"),
+            ("selected-fg", "#93a1a1"),
+            ("selected-bg", "#073642"),
+            ("matched-selected-fg", "#b58900"),
+            ("tag-fg", "#268bd2"),
+            ("header-fg", "#586e75"),
+        ],
+    ),
+];
+
+fn config_path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .map(|d| d.join("star/config"))
+}
+
+type Themes = HashMap<String, Vec<(String, String)>>;
+
+// themes are sections of the config file headed `[theme NAME]`, followed by `key = color` lines
+// naming the colors like the --color-* options without their prefix. blank lines and lines
+// starting with # are ignored, as are other sections, which are left for other settings
+fn parse_themes(contents: &str) -> std::result::Result<Themes, String> {
+    let mut themes = HashMap::new();
+    let mut current: Option<&mut Vec<(String, String)>> = None;
+    for (n, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = match section.trim().strip_prefix("theme ") {
+                Some(name) => {
+                    let theme: &mut Vec<_> = themes.entry(name.trim().to_string()).or_default();
+                    // a theme given twice is replaced, like any other repeated setting
+                    theme.clear();
+                    Some(theme)
+                }
+                None => None,
+            };
+            continue;
+        }
+        let Some(theme) = current.as_mut() else {
+            continue;
+        };
+        let (key, value) = line
+            .split_once('=')
+            .map(|(k, v)| (k.trim(), v.trim()))
+            .ok_or_else(|| format!("line {}: expected \"key = color\"", n + 1))?;
+        if !COLOR_KEYS.contains(&key) {
+            return Err(format!("line {}: unknown color \"{}\"", n + 1, key));
+        }
+        theme.push((key.to_string(), value.to_string()));
+    }
+    Ok(themes)
+}
+
+// returns the colors a theme sets. themes in the config file take precedence over built-in ones of
+// the same name
+pub fn find(name: &str) -> Result<HashMap<String, String>> {
+    if let Some(path) = config_path() {
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                let mut themes = parse_themes(&contents).map_err(|e| {
+                    Error::new(ErrorKind::InvalidData, format!("{}: {}", path.display(), e))
+                })?;
+                if let Some(theme) = themes.remove(name) {
+                    return Ok(theme.into_iter().collect());
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    THEMES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, colors)| {
+            colors
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        })
+        .ok_or_else(|| {
+            let names: Vec<&str> = THEMES.iter().map(|(n, _)| *n).collect();
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "unknown theme: \"{}\" (built-in themes: {})",
+                    name,
+                    names.join(", ")
+                ),
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_themes() {
+        let contents = "\
+# my themes
+[theme ocean]
+matched-fg = #5fd7ff
+selected-bg=24
+
+[other]
+whatever = 1
+
+[theme  plain ]
+tag-fg = red
+";
+        let themes = parse_themes(contents).unwrap();
+        assert_eq!(
+            themes["ocean"],
+            vec![
+                ("matched-fg".to_string(), "#5fd7ff".to_string()),
+                ("selected-bg".to_string(), "24".to_string()),
+            ]
+        );
+        assert_eq!(
+            themes["plain"],
+            vec![("tag-fg".to_string(), "red".to_string())]
+        );
+        assert_eq!(themes.len(), 2);
+        assert_eq!(
+            parse_themes("[theme x]\nmatched = red").unwrap_err(),
+            "line 2: unknown color \"matched\""
+        );
+        assert!(parse_themes("[theme x]\nmatched-fg red").is_err());
+    }
+
+    #[test]
+    fn test_builtin_themes() {
+        for (_, colors) in THEMES {
+            assert!(colors.iter().all(|(key, _)| COLOR_KEYS.contains(key)));
+        }
+    }
+}
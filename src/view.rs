@@ -0,0 +1,235 @@
+use crate::matches::Matches;
+use star::{Order, Score};
+use std::{
+    cmp::{max, min},
+    ops::Index,
+    sync::Arc,
+};
+
+// the matches for the current query, in the order they're shown. the matches are shared with the
+// score cache, so they're never sorted in place: while they're in the view's order and already
+// sorted as far as it's read they're used directly, and otherwise the view sorts its own list of
+// indices into them.
+#[derive(Default)]
+pub struct View<'a> {
+    matches: Arc<Matches<'a>>,
+    order: Order,
+    // `None` until the view is read past what the matches have sorted
+    indices: Option<Vec<usize>>,
+    sorted: usize,
+}
+
+impl<'a> View<'a> {
+    pub fn new(matches: Arc<Matches<'a>>, order: Order) -> Self {
+        // matches sorted in another order aren't sorted at all as far as the view is concerned
+        let sorted = if matches.order() == order {
+            matches.sorted()
+        } else {
+            0
+        };
+        Self {
+            matches,
+            order,
+            indices: None,
+            sorted,
+        }
+    }
+
+    pub fn matches(&self) -> &Arc<Matches<'a>> {
+        &self.matches
+    }
+
+    pub fn len(&self) -> usize {
+        self.matches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matches.is_empty()
+    }
+
+    pub fn get(&self, i: usize) -> Option<&Score<'a>> {
+        match &self.indices {
+            Some(indices) => indices.get(i).map(|&i| &self.matches[i]),
+            None => self.matches.get(i),
+        }
+    }
+
+    // the matches in the view's order, as far as it's sorted
+    pub fn iter(&self) -> impl Iterator<Item = &Score<'a>> {
+        (0..self.len()).map(|i| &self.matches[self.position(i)])
+    }
+
+    fn position(&self, i: usize) -> usize {
+        self.indices.as_ref().map_or(i, |indices| indices[i])
+    }
+
+    // make sure the first `n` matches are in order. every extension has to partition all of the
+    // unsorted matches, so the sorted window at least doubles each time to keep scrolling cheap.
+    pub fn sort_to(&mut self, n: usize) {
+        if n <= self.sorted {
+            return;
+        }
+        let n = min(max(n, self.sorted * 2), self.len());
+        let (matches, order) = (&self.matches, self.order);
+        let cmp = |a: &usize, b: &usize| order.cmp(&matches[*a], &matches[*b]);
+        let indices = self
+            .indices
+            .get_or_insert_with(|| (0..matches.len()).collect());
+        let unsorted = &mut indices[self.sorted..];
+        let needed = n - self.sorted;
+        if needed < unsorted.len() {
+            unsorted.select_nth_unstable_by(needed, cmp);
+        }
+        unsorted[..needed].sort_unstable_by(cmp);
+        self.sorted = n;
+    }
+}
+
+impl<'a> Index<usize> for View<'a> {
+    type Output = Score<'a>;
+
+    fn index(&self, i: usize) -> &Self::Output {
+        &self.matches[self.position(i)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use star::{Line, Matcher, Order, Selecta};
+    use std::time::{Duration, Instant};
+
+    fn lengths(view: &View, n: usize) -> Vec<usize> {
+        view.iter().take(n).map(|s| s.line.len()).collect()
+    }
+
+    #[test]
+    fn test_sort_to() {
+        let lines: Vec<Line> = (0..10)
+            .rev()
+            .map(|n| Line::new("x".repeat(n + 1), n))
+            .collect();
+        let scores = lines
+            .iter()
+            .filter_map(|l| Selecta::default().score(l, &[]))
+            .collect();
+        // only the first three are in order
+        let matches = Arc::new(Matches::new(scores, 3, Order::default()));
+        let mut view = View::new(Arc::clone(&matches), Order::default());
+        assert_eq!(view.sorted, 3);
+        assert_eq!(view.len(), 10);
+        assert_eq!(lengths(&view, 3), vec![1, 2, 3]);
+        assert!(view.iter().skip(3).all(|s| s.line.len() > 3));
+        // extending the sorted window keeps what was already sorted, and leaves the shared
+        // matches alone
+        view.sort_to(6);
+        assert_eq!(view.sorted, 6);
+        assert_eq!(lengths(&view, 6), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(view.get(5).unwrap().line.len(), 6);
+        assert_eq!(matches.sorted(), 3);
+        // asking for a smaller window does nothing
+        view.sort_to(2);
+        assert_eq!(view.sorted, 6);
+        // asking for more than there are sorts everything
+        view.sort_to(100);
+        assert_eq!(view.sorted, 10);
+        assert_eq!(lengths(&view, 10), (1..=10).collect::<Vec<_>>());
+        assert!(view.get(10).is_none());
+    }
+
+    #[test]
+    fn test_order() {
+        let lines = [
+            Line::new("x_foo".to_string(), 0),
+            Line::new("f_o_o_x".to_string(), 1),
+            Line::new("foo".to_string(), 2),
+        ];
+        let scores = lines
+            .iter()
+            .filter_map(|l| Selecta::default().score(l, &['f', 'o', 'o']))
+            .collect();
+        let matches = Arc::new(Matches::new(scores, 3, Order::default()));
+        let indices = |view: &View| view.iter().map(|s| s.line.index).collect::<Vec<_>>();
+        // matches already in the view's order are used as they are
+        let view = View::new(Arc::clone(&matches), Order::default());
+        assert_eq!(view.sorted, 3);
+        assert!(view.indices.is_none());
+        assert_eq!(indices(&view), vec![2, 0, 1]);
+        // otherwise none of them count as sorted, and the view sorts them itself
+        let mut view = View::new(Arc::clone(&matches), Order::Input);
+        assert_eq!(view.sorted, 0);
+        view.sort_to(3);
+        assert_eq!(indices(&view), vec![0, 1, 2]);
+        assert_eq!(matches.order(), Order::default());
+        assert_eq!(
+            matches.iter().map(|s| s.line.index).collect::<Vec<_>>(),
+            vec![2, 0, 1]
+        );
+    }
+
+    // compares sorting every match with sorting the first window and then scrolling through the
+    // view one line at a time. run with `cargo test --release -- --ignored --nocapture bench`
+    #[test]
+    #[ignore]
+    fn bench_sort() {
+        const WINDOW: usize = 20;
+        const SCROLL: usize = 1000;
+        let best = |f: &mut dyn FnMut()| -> Duration {
+            (0..5)
+                .map(|_| {
+                    let start = Instant::now();
+                    f();
+                    start.elapsed()
+                })
+                .min()
+                .unwrap()
+        };
+        let mut seed: u64 = 1;
+        let mut random = move || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as usize
+        };
+        println!(
+            "lines      matches   full sort   top-{}   scrolling {} further",
+            WINDOW, SCROLL
+        );
+        for n in [100_000, 500_000, 2_000_000] {
+            let lines: Vec<Line> = (0..n)
+                .map(|i| {
+                    let len = 10 + random() % 50;
+                    let buf = (0..len).map(|_| (b'a' + (random() % 26) as u8) as char);
+                    Line::new(buf.collect(), i)
+                })
+                .collect();
+            let scores: Vec<_> = lines
+                .iter()
+                .filter_map(|l| Selecta::default().score(l, &['a', 'b']))
+                .collect();
+            let order = Order::default();
+            let full = best(&mut || {
+                let mut scores = scores.clone();
+                scores.sort_unstable_by(|a, b| order.cmp(a, b));
+            });
+            let top = best(&mut || {
+                Matches::new(scores.clone(), WINDOW, order);
+            });
+            let matches = Arc::new(Matches::new(scores.clone(), WINDOW, order));
+            let scroll = best(&mut || {
+                let mut view = View::new(Arc::clone(&matches), order);
+                for offset in 1..=SCROLL {
+                    view.sort_to(offset + WINDOW);
+                }
+            });
+            println!(
+                "{:<10} {:<9} {:<11.2?} {:<8.2?} {:.2?}",
+                n,
+                scores.len(),
+                full,
+                top,
+                scroll
+            );
+        }
+    }
+}
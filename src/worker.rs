@@ -0,0 +1,203 @@
+use crate::matches::Matches;
+use rayon::prelude::*;
+use star::{Line, Matcher, Order, Score};
+use std::{
+    cmp::min,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::Receiver,
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+// lines are scored in chunks so that a stale job can be abandoned, and partial results published,
+// without waiting for the whole input to be scored.
+const CHUNK_SIZE: usize = 10_000;
+const PUBLISH_INTERVAL: Duration = Duration::from_millis(50);
+
+pub struct Job<'a> {
+    pub generation: usize,
+    pub query: Vec<char>,
+    pub matcher: &'a dyn Matcher,
+    pub order: Order,
+    // `None` means every input line is a candidate
+    pub candidates: Option<Arc<Matches<'a>>>,
+}
+
+#[derive(Debug)]
+pub struct Update<'a> {
+    pub generation: usize,
+    pub query: Vec<char>,
+    pub scores: Arc<Matches<'a>>,
+    pub done: bool,
+}
+
+// `window` is how many of the best matches need to be in order when results are published; the
+// rest are sorted lazily, if ever, as they are scrolled into view.
+pub fn run<'a>(
+    lines: &'a [Line],
+    window: usize,
+    jobs: Receiver<Job<'a>>,
+    generation: &AtomicUsize,
+    publish: impl Fn(Update<'a>),
+) {
+    while let Ok(mut job) = jobs.recv() {
+        // if the query changed again while the last job was running, only the newest job matters
+        while let Ok(newer) = jobs.try_recv() {
+            job = newer;
+        }
+        score(lines, window, &job, generation, &publish);
+    }
+}
+
+fn score<'a>(
+    lines: &'a [Line],
+    window: usize,
+    job: &Job<'a>,
+    generation: &AtomicUsize,
+    publish: &impl Fn(Update<'a>),
+) {
+    let is_current = || generation.load(Ordering::SeqCst) == job.generation;
+    let total = match &job.candidates {
+        Some(candidates) => candidates.len(),
+        None => lines.len(),
+    };
+    let mut scores: Vec<Score> = vec![];
+    let mut last_publish = Instant::now();
+    for start in (0..total).step_by(CHUNK_SIZE) {
+        if !is_current() {
+            return;
+        }
+        let end = min(start + CHUNK_SIZE, total);
+        match &job.candidates {
+            Some(candidates) => scores.par_extend(
+                candidates[start..end]
+                    .par_iter()
+                    .filter_map(|s| job.matcher.score(s.line, &job.query)),
+            ),
+            None => scores.par_extend(
+                lines[start..end]
+                    .par_iter()
+                    .filter_map(|l| job.matcher.score(l, &job.query)),
+            ),
+        }
+        if end < total && last_publish.elapsed() >= PUBLISH_INTERVAL {
+            publish(Update {
+                generation: job.generation,
+                query: job.query.clone(),
+                scores: Arc::new(Matches::new(scores.clone(), window, job.order)),
+                done: false,
+            });
+            last_publish = Instant::now();
+        }
+    }
+    if !is_current() {
+        return;
+    }
+    publish(Update {
+        generation: job.generation,
+        query: job.query.clone(),
+        scores: Arc::new(Matches::new(scores, window, job.order)),
+        done: true,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use star::Selecta;
+    use std::{cell::RefCell, sync::mpsc::channel};
+
+    #[test]
+    fn test_score() {
+        let matcher = Selecta::default();
+        let lines = [
+            Line::new("foo".to_string(), 0),
+            Line::new("bar".to_string(), 1),
+            Line::new("baz".to_string(), 2),
+        ];
+        let generation = AtomicUsize::new(1);
+        let updates = RefCell::new(vec![]);
+        let publish = |u| updates.borrow_mut().push(u);
+        // every line is a candidate
+        let job = Job {
+            generation: 1,
+            query: vec!['b'],
+            matcher: &matcher,
+            order: Order::default(),
+            candidates: None,
+        };
+        score(&lines, 10, &job, &generation, &publish);
+        assert_eq!(updates.borrow().len(), 1);
+        let update = updates.borrow_mut().pop().unwrap();
+        assert!(update.done);
+        assert_eq!(update.query, vec!['b']);
+        assert_eq!(update.scores.len(), 2);
+        // only the previous scores are candidates
+        let job = Job {
+            generation: 1,
+            query: vec!['b', 'a', 'z'],
+            matcher: &matcher,
+            order: Order::default(),
+            candidates: Some(Arc::clone(&update.scores)),
+        };
+        score(&lines, 10, &job, &generation, &publish);
+        let update = updates.borrow_mut().pop().unwrap();
+        assert_eq!(update.scores.len(), 1);
+        assert_eq!(update.scores[0].line.buf, "baz");
+    }
+
+    #[test]
+    fn test_score_stale_job() {
+        let matcher = Selecta::default();
+        let lines = [
+            Line::new("foo".to_string(), 0),
+            Line::new("bar".to_string(), 1),
+            Line::new("baz".to_string(), 2),
+        ];
+        let generation = AtomicUsize::new(2);
+        let updates = RefCell::new(vec![]);
+        let job = Job {
+            generation: 1,
+            query: vec!['b'],
+            matcher: &matcher,
+            order: Order::default(),
+            candidates: None,
+        };
+        let publish = |u| updates.borrow_mut().push(u);
+        score(&lines, 10, &job, &generation, &publish);
+        assert!(updates.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_run_skips_to_newest_job() {
+        let matcher = Selecta::default();
+        let lines = [
+            Line::new("foo".to_string(), 0),
+            Line::new("bar".to_string(), 1),
+            Line::new("baz".to_string(), 2),
+        ];
+        let generation = AtomicUsize::new(2);
+        let updates = RefCell::new(vec![]);
+        let (jobs_tx, jobs) = channel();
+        for (generation, query) in [(1, vec!['f']), (2, vec!['b'])] {
+            jobs_tx
+                .send(Job {
+                    generation,
+                    query,
+                    matcher: &matcher,
+                    order: Order::default(),
+                    candidates: None,
+                })
+                .unwrap();
+        }
+        drop(jobs_tx);
+        let publish = |u| updates.borrow_mut().push(u);
+        run(&lines, 10, jobs, &generation, publish);
+        let updates = updates.borrow();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].generation, 2);
+        assert_eq!(updates[0].scores.len(), 2);
+    }
+}